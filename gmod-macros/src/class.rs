@@ -0,0 +1,352 @@
+use proc_macro2::TokenStream;
+use syn::{parse::Parse, spanned::Spanned, Attribute, FnArg, ImplItem, ItemImpl, ItemStruct, Lit, Meta, NestedMeta};
+
+#[derive(Default)]
+pub struct LuaAttr {
+	pub name: Option<String>,
+	pub get: bool,
+	pub set: bool,
+	pub skip: bool,
+}
+
+/// Parses and removes any `#[lua(...)]` attributes.
+pub fn take_lua_attrs(attrs: &mut Vec<Attribute>) -> Result<LuaAttr, syn::Error> {
+	let mut lua_attr = LuaAttr::default();
+	let mut result = Ok(());
+	attrs.retain(|attr| {
+		if !attr.path.is_ident("lua") {
+			return true;
+		}
+		if let Err(err) = parse_lua_attr(attr, &mut lua_attr) {
+			result = Err(err);
+		}
+		false
+	});
+	result.map(|_| lua_attr)
+}
+
+fn parse_lua_attr(attr: &Attribute, lua_attr: &mut LuaAttr) -> Result<(), syn::Error> {
+	let list = match attr.parse_meta()? {
+		Meta::List(list) => list,
+		meta => return Err(syn::Error::new(meta.span(), "expected #[lua(...)]")),
+	};
+	for nested in list.nested {
+		match nested {
+			NestedMeta::Meta(Meta::Path(path)) if path.is_ident("get") => lua_attr.get = true,
+			NestedMeta::Meta(Meta::Path(path)) if path.is_ident("set") => lua_attr.set = true,
+			NestedMeta::Meta(Meta::Path(path)) if path.is_ident("skip") => lua_attr.skip = true,
+			NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("name") => match nv.lit {
+				Lit::Str(name) => lua_attr.name = Some(name.value()),
+				lit => return Err(syn::Error::new(lit.span(), "expected a string")),
+			},
+			nested => return Err(syn::Error::new(nested.span(), "unknown option, expected `get`, `set`, `skip` or `name = \"...\"`")),
+		}
+	}
+	Ok(())
+}
+
+/// Arguments of `#[lua_class(...)]`
+#[derive(Default)]
+pub struct ClassArgs {
+	name: Option<String>,
+	meta: Vec<syn::Path>,
+	call: Option<syn::Expr>,
+}
+impl Parse for ClassArgs {
+	fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+		let mut args = ClassArgs::default();
+		while !input.is_empty() {
+			let ident: syn::Ident = input.parse()?;
+			if ident == "name" {
+				input.parse::<Token![=]>()?;
+				args.name = Some(input.parse::<syn::LitStr>()?.value());
+			} else if ident == "meta" {
+				let content;
+				parenthesized!(content in input);
+				args.meta.extend(content.parse_terminated::<syn::Path, Token![,]>(syn::Path::parse)?);
+			} else if ident == "call" {
+				input.parse::<Token![=]>()?;
+				args.call = Some(input.parse()?);
+			} else {
+				return Err(syn::Error::new(ident.span(), "unknown option, expected `name = \"...\"`, `meta(...)` or `call = ...`"));
+			}
+			if !input.is_empty() {
+				input.parse::<Token![,]>()?;
+			}
+		}
+		Ok(args)
+	}
+}
+
+/// A metamethod provided by a trait in `#[lua_class(meta(...))]`.
+struct Metamethod {
+	name: &'static str,
+
+	/// The `LuaFunction` implementing it
+	func: TokenStream,
+
+	/// For arithmetic metamethods, a call which returns `false` instead of raising an error if the operands aren't the types it's for, so that several can share the metamethod
+	try_call: Option<TokenStream>,
+}
+
+/// Maps a trait in `#[lua_class(meta(...))]` to the metamethods it provides.
+fn metamethods(ident: &syn::Ident, path: &syn::Path) -> Result<Vec<Metamethod>, syn::Error> {
+	let segment = match path.segments.last() {
+		Some(segment) if path.segments.len() == 1 => segment,
+		_ => return Err(syn::Error::new(path.span(), "expected a trait name, such as `Display` or `Mul<f64>`")),
+	};
+
+	let rhs = match &segment.arguments {
+		syn::PathArguments::None => None,
+		syn::PathArguments::AngleBracketed(args) if args.args.len() == 1 => Some(&args.args[0]),
+		arguments => return Err(syn::Error::new(arguments.span(), "expected a single type argument")),
+	};
+
+	let trait_name = segment.ident.to_string();
+	let binary = match trait_name.as_str() {
+		"Add" => Some(("__add", "add")),
+		"Sub" => Some(("__sub", "sub")),
+		"Mul" => Some(("__mul", "mul")),
+		"Div" => Some(("__div", "div")),
+		_ => None,
+	};
+	if let Some((name, op)) = binary {
+		let (func, try_call) = match rhs {
+			Some(rhs) => {
+				let (func, try_func) = (format_ident!("{}_rhs", op), format_ident!("__try_{}_rhs", op));
+				(quote!(::gmod::userdata::meta::#func::<#ident, #rhs>), quote!(::gmod::userdata::meta::#try_func::<#ident, #rhs>(lua)))
+			},
+			None => {
+				let (func, try_func) = (format_ident!("{}", op), format_ident!("__try_{}", op));
+				(quote!(::gmod::userdata::meta::#func::<#ident>), quote!(::gmod::userdata::meta::#try_func::<#ident>(lua)))
+			},
+		};
+		return Ok(vec![Metamethod { name, func, try_call: Some(try_call) }]);
+	}
+
+	if rhs.is_some() {
+		return Err(syn::Error::new(segment.arguments.span(), "only `Add`, `Sub`, `Mul` and `Div` can take a right hand side type"));
+	}
+	let unary = |name, func: &str| {
+		let func = format_ident!("{}", func);
+		Metamethod { name, func: quote!(::gmod::userdata::meta::#func::<#ident>), try_call: None }
+	};
+	Ok(match trait_name.as_str() {
+		"Display" => vec![unary("__tostring", "tostring")],
+		"PartialEq" => vec![unary("__eq", "eq")],
+		"PartialOrd" => vec![unary("__lt", "lt"), unary("__le", "le")],
+		"Neg" => vec![unary("__unm", "unm")],
+		"LuaLen" => vec![unary("__len", "len")],
+		_ => return Err(syn::Error::new(
+			segment.ident.span(),
+			"unsupported trait, expected `Display`, `PartialEq`, `PartialOrd`, `Add`, `Sub`, `Mul`, `Div`, `Neg` or `LuaLen`",
+		)),
+	})
+}
+
+/// Sets the metamethods from `#[lua_class(meta(...))]`.
+///
+/// Arithmetic traits with different right hand side types, such as `Mul` and `Mul<f64>`, share one metamethod which tries each in turn. Anything else can only be set once.
+fn build_metamethods(ident: &syn::Ident, paths: &[syn::Path]) -> Result<Vec<TokenStream>, syn::Error> {
+	let mut grouped: Vec<(&'static str, Vec<Metamethod>)> = Vec::new();
+	for path in paths {
+		for metamethod in metamethods(ident, path)? {
+			match grouped.iter_mut().find(|(name, _)| *name == metamethod.name) {
+				Some((name, group)) => {
+					let duplicate = metamethod.try_call.is_none()
+						|| group.iter().any(|other| other.try_call.as_ref().map(ToString::to_string) == metamethod.try_call.as_ref().map(ToString::to_string));
+					if duplicate {
+						return Err(syn::Error::new(path.span(), format!("`{}` is already set by another trait in `meta(...)`", name)));
+					}
+					group.push(metamethod);
+				},
+				None => grouped.push((metamethod.name, vec![metamethod])),
+			}
+		}
+	}
+
+	Ok(grouped.into_iter().map(|(name, mut group)| {
+		if group.len() == 1 {
+			let func = group.pop().unwrap().func;
+			return quote!(methods.add_meta(#name, #func););
+		}
+
+		let try_calls = group.into_iter().map(|metamethod| metamethod.try_call.unwrap());
+		quote! {{
+			unsafe extern "C-unwind" fn __gmod_meta(lua: ::gmod::lua::State) -> i32 {
+				#(if #try_calls { return 1; })*
+				::gmod::userdata::meta::__no_overload(lua, #name)
+			}
+			methods.add_meta(#name, __gmod_meta);
+		}}
+	}).collect())
+}
+
+pub fn lua_class(args: ClassArgs, mut input: ItemStruct) -> Result<TokenStream, syn::Error> {
+	if !input.generics.params.is_empty() {
+		return Err(syn::Error::new(input.generics.span(), "#[lua_class] types can't be generic"));
+	}
+
+	let ident = input.ident.clone();
+	let name = args.name.unwrap_or_else(|| ident.to_string());
+
+	let mut meta = build_metamethods(&ident, &args.meta)?;
+	if let Some(call) = &args.call {
+		meta.push(quote! {{
+			#[allow(unused_unsafe)]
+			unsafe extern "C-unwind" fn __gmod_call(lua: ::gmod::lua::State) -> i32 {
+				::gmod::lua::ValuesReturned::from(unsafe { ::gmod::userdata::meta::__call::<#ident, _, _>(lua, #call) }).into()
+			}
+			methods.add_meta("__call", __gmod_call);
+		}});
+	}
+
+	let mut fields = Vec::new();
+	for field in input.fields.iter_mut() {
+		let lua_attr = take_lua_attrs(&mut field.attrs)?;
+		if !lua_attr.get && !lua_attr.set {
+			continue;
+		}
+
+		let field_ident = match &field.ident {
+			Some(field_ident) => field_ident.clone(),
+			None => return Err(syn::Error::new(field.span(), "#[lua(get, set)] is only supported on named fields")),
+		};
+		let lua_name = lua_attr.name.unwrap_or_else(|| field_ident.to_string());
+
+		if lua_attr.get {
+			fields.push(quote! {{
+				unsafe extern "C-unwind" fn __gmod_get(lua: ::gmod::lua::State) -> i32 {
+					let value = ::std::clone::Clone::clone(&lua.check_class::<#ident>(1).#field_ident);
+					::gmod::lua::ForcePushToLua::force_push_to_lua(value, lua);
+					1
+				}
+				fields.add_getter(#lua_name, __gmod_get);
+			}});
+		}
+		if lua_attr.set {
+			fields.push(quote! {{
+				unsafe extern "C-unwind" fn __gmod_set(lua: ::gmod::lua::State) -> i32 {
+					let value = lua.check(2);
+					lua.check_class_mut::<#ident>(1).#field_ident = value;
+					0
+				}
+				fields.add_setter(#lua_name, __gmod_set);
+			}});
+		}
+	}
+
+	Ok(quote! {
+		#input
+
+		impl ::gmod::userdata::LuaUserData for #ident {
+			const NAME: &'static ::std::ffi::CStr = ::gmod::cstr::cstr!(#name);
+
+			fn register_methods(methods: &mut ::gmod::userdata::UserDataMethods<Self>) {
+				#[allow(unused_imports)]
+				use ::gmod::userdata::__LuaClassNoMethods as _;
+				Self::__gmod_register_methods(methods);
+				#(#meta)*
+			}
+
+			#[allow(unused_variables)]
+			fn register_fields(fields: &mut ::gmod::userdata::UserDataFields<Self>) {
+				#(#fields)*
+			}
+		}
+	})
+}
+
+pub fn lua_methods(mut input: ItemImpl) -> Result<TokenStream, syn::Error> {
+	if !input.generics.params.is_empty() {
+		return Err(syn::Error::new(input.generics.span(), "#[lua_methods] can't be used on generic impl blocks"));
+	}
+	if let Some((_, path, _)) = &input.trait_ {
+		return Err(syn::Error::new(path.span(), "#[lua_methods] can't be used on trait impl blocks"));
+	}
+
+	let self_ty = input.self_ty.clone();
+
+	let mut methods = Vec::new();
+	for item in input.items.iter_mut() {
+		let method = match item {
+			ImplItem::Method(method) => method,
+			_ => continue,
+		};
+
+		let lua_attr = take_lua_attrs(&mut method.attrs)?;
+		if lua_attr.skip {
+			continue;
+		}
+
+		let method_ident = method.sig.ident.clone();
+		let lua_name = lua_attr.name.unwrap_or_else(|| method_ident.to_string());
+
+		let add = if lua_name.starts_with("__") {
+			quote!(add_meta)
+		} else {
+			quote!(add)
+		};
+
+		// A #[lua_function] already has a `lua_` function to register, as long as it isn't turned into a Lua function in place
+		let lua_function = method.attrs.iter_mut().find(|attr| attr.path.segments.last().map(|segment| segment.ident == "lua_function").unwrap_or(false));
+		if let Some(attr) = lua_function {
+			if !matches!(method.sig.inputs.first(), Some(FnArg::Receiver(_))) {
+				attr.tokens = quote!((associated));
+			}
+			let shim = format_ident!("lua_{}", method_ident);
+			methods.push(quote!(methods.#add(#lua_name, <#self_ty>::#shim);));
+			continue;
+		}
+
+		let call = match method.sig.inputs.first() {
+			Some(FnArg::Receiver(receiver)) => {
+				if receiver.reference.is_none() {
+					return Err(syn::Error::new(receiver.span(), "methods must take `&self` or `&mut self`"));
+				}
+				if method.sig.inputs.len() != 2 {
+					return Err(syn::Error::new(method.sig.inputs.span(), "methods must take `self` and the Lua state (gmod::lua::State)"));
+				}
+				if receiver.mutability.is_some() {
+					quote!({
+						let mut this = lua.check_class_mut::<#self_ty>(1);
+						<#self_ty>::#method_ident(&mut this, lua)
+					})
+				} else {
+					quote!({
+						let this = lua.check_class::<#self_ty>(1);
+						<#self_ty>::#method_ident(&this, lua)
+					})
+				}
+			},
+			_ => {
+				if method.sig.inputs.len() != 1 {
+					return Err(syn::Error::new(method.sig.inputs.span(), "associated functions must take only the Lua state (gmod::lua::State)"));
+				}
+				quote!(<#self_ty>::#method_ident(lua))
+			},
+		};
+
+		methods.push(quote! {{
+			#[allow(unused_unsafe)]
+			unsafe extern "C-unwind" fn __gmod_method(lua: ::gmod::lua::State) -> i32 {
+				// The userdata is released before the return value is converted, which raises an error for an `Err`
+				let ret = unsafe { #call };
+				::gmod::lua::ValuesReturned::from(ret).into()
+			}
+			methods.#add(#lua_name, __gmod_method);
+		}});
+	}
+
+	Ok(quote! {
+		#input
+
+		impl #self_ty {
+			#[doc(hidden)]
+			#[allow(unused_variables)]
+			pub fn __gmod_register_methods(methods: &mut ::gmod::userdata::UserDataMethods<Self>) {
+				#(#methods)*
+			}
+		}
+	})
+}
@@ -0,0 +1,254 @@
+use proc_macro2::TokenStream;
+use quote::ToTokens;
+use syn::{parse::Parse, spanned::Spanned, FnArg, ItemFn, LitStr, Type};
+
+/// Whether this argument is the Lua state.
+pub fn is_state_arg(arg: &FnArg) -> bool {
+	match arg {
+		FnArg::Typed(arg) => match &*arg.ty {
+			Type::Path(path) => path.qself.is_none() && path.path.segments.last().map(|segment| segment.ident == "State").unwrap_or(false),
+			_ => false,
+		},
+		FnArg::Receiver(_) => false,
+	}
+}
+
+/// Whether this is a plain `fn(lua: State)`, which is exported as is, rather than a typed function which needs a shim.
+pub fn is_typed(input: &ItemFn) -> bool {
+	input.sig.inputs.len() != 1 || !is_state_arg(&input.sig.inputs[0])
+}
+
+/// Arguments of `#[lua_function(...)]`
+#[derive(Default)]
+pub struct FunctionArgs {
+	associated: bool,
+}
+impl Parse for FunctionArgs {
+	fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+		let mut args = FunctionArgs::default();
+		if !input.is_empty() {
+			let ident: syn::Ident = input.parse()?;
+			if ident != "associated" {
+				return Err(syn::Error::new(ident.span(), "unknown option, expected `associated`"));
+			}
+			args.associated = true;
+		}
+		Ok(args)
+	}
+}
+
+/// Expands `#[lua_function]`
+pub fn lua_function(args: FunctionArgs, mut input: ItemFn) -> Result<TokenStream, syn::Error> {
+	if args.associated {
+		if let Some(FnArg::Receiver(receiver)) = input.sig.inputs.first() {
+			return Err(syn::Error::new(receiver.span(), "`associated` is for functions without `self`, methods always keep their `self` and get a `lua_` function"));
+		}
+		return associated_lua_function(input);
+	}
+
+	if is_typed(&input) {
+		return typed_lua_function(input);
+	}
+
+	// Make sure it's valid
+	crate::check_lua_function(&mut input)?;
+
+	// Make the return type nice and dynamic
+	crate::genericify_return(&mut input);
+
+	Ok(input.into_token_stream())
+}
+
+/// Expands `#[lua_export("path")]`, a `#[lua_function]` which is also added to the list of exports registered by `#[gmod13_open]`.
+pub fn lua_export(path: LitStr, input: ItemFn) -> Result<TokenStream, syn::Error> {
+	if path.value().split('.').any(|segment| segment.is_empty()) {
+		return Err(syn::Error::new(path.span(), "expected a dotted path, such as \"mymod.func\""));
+	}
+
+	let ident = input.sig.ident.clone();
+	let func = lua_function(FunctionArgs::default(), input)?;
+	Ok(quote! {
+		#func
+
+		const _: () = {
+			#[::gmod::linkme::distributed_slice(::gmod::lua::__LUA_EXPORTS)]
+			#[linkme(crate = ::gmod::linkme)]
+			static EXPORT: ::gmod::lua::LuaExport = ::gmod::lua::LuaExport { path: #path, func: #ident };
+		};
+	})
+}
+
+/// Rejects signatures which can't be turned into a shim.
+fn check_signature(sig: &syn::Signature) -> Result<(), syn::Error> {
+	if let Some(asyncness) = &sig.asyncness {
+		return Err(syn::Error::new(asyncness.span(), "Cannot be async"));
+	}
+	if let Some(constness) = &sig.constness {
+		return Err(syn::Error::new(constness.span(), "Cannot be const"));
+	}
+	if let Some(abi) = &sig.abi {
+		return Err(syn::Error::new(abi.span(), "Do not specify an ABI"));
+	}
+	if !sig.generics.params.is_empty() {
+		return Err(syn::Error::new(sig.generics.span(), "Lua functions can't be generic"));
+	}
+	Ok(())
+}
+
+/// Reads typed arguments from the Lua stack, starting at `__gmod_arg`.
+///
+/// Returns the statements reading them and the arguments to call the original function with, which start with the Lua state if it takes it first.
+/// Whether `ty` is `Variadic`, which takes every remaining argument. Only the name can be checked, so a renamed import isn't caught.
+fn is_variadic(ty: &Type) -> bool {
+	match ty {
+		Type::Path(path) => path.qself.is_none() && path.path.segments.last().map(|segment| segment.ident == "Variadic").unwrap_or(false),
+		_ => false,
+	}
+}
+
+fn read_args<'a>(args: impl Iterator<Item = &'a FnArg>) -> Result<(Vec<TokenStream>, Vec<TokenStream>), syn::Error> {
+	let mut args = args.peekable();
+
+	// Pass the Lua state through if the function wants it
+	let mut call_args = Vec::new();
+	if let Some(arg) = args.peek() {
+		if is_state_arg(arg) {
+			args.next();
+			call_args.push(quote!(lua));
+		}
+	}
+
+	let mut reads = Vec::new();
+	while let Some(arg) = args.next() {
+		let i = reads.len();
+		let ty = match arg {
+			FnArg::Typed(arg) => &arg.ty,
+			FnArg::Receiver(receiver) => return Err(syn::Error::new(receiver.span(), "`self` must be the first argument")),
+		};
+		if is_variadic(ty) && args.peek().is_some() {
+			return Err(syn::Error::new(ty.span(), "only the last argument can be `Variadic`, as it takes every remaining argument"));
+		}
+		let name = format_ident!("__gmod_arg{}", i);
+		reads.push(quote_spanned!(ty.span()=> let #name = ::gmod::lua::__check_arg::<#ty>(lua, &mut __gmod_arg);));
+		call_args.push(quote!(#name));
+	}
+
+	Ok((reads, call_args))
+}
+
+/// Turns a function with typed arguments and return values into a `LuaFunction`.
+///
+/// The original function is kept inside the shim as a closure, which reads each argument with `FromLuaMulti` (raising a "bad argument" error if it can't) and pushes the return values.
+pub fn typed_lua_function(input: ItemFn) -> Result<TokenStream, syn::Error> {
+	check_signature(&input.sig)?;
+
+	if let Some(FnArg::Receiver(_)) = input.sig.inputs.first() {
+		return method_lua_function(input);
+	}
+
+	let (reads, call_args) = read_args(input.sig.inputs.iter())?;
+
+	let ItemFn { attrs, vis, sig, block } = input;
+	let ident = &sig.ident;
+	let unsafety = &sig.unsafety;
+	let inputs = &sig.inputs;
+	let output = &sig.output;
+
+	Ok(quote! {
+		#(#attrs)*
+		#vis #unsafety extern "C-unwind" fn #ident(lua: ::gmod::lua::State) -> i32 {
+			let __gmod_fn = |#inputs| #output #block;
+
+			#[allow(unused_unsafe, unused_mut)]
+			unsafe {
+				let mut __gmod_arg = 1;
+				#(#reads)*
+				::gmod::lua::__LuaReturn::__push_return(__gmod_fn(#(#call_args),*), lua)
+			}
+		}
+	})
+}
+
+/// Turns a `&self` or `&mut self` method of a userdata type into a `LuaFunction` named `lua_<method>`, which is called with the userdata as its first argument.
+///
+/// The method itself is kept as it is. A method taking only the Lua state returns like a plain `#[lua_function]`, otherwise its arguments and return values are typed.
+fn method_lua_function(input: ItemFn) -> Result<TokenStream, syn::Error> {
+	let mut args = input.sig.inputs.iter();
+	let (borrow, this) = match args.next() {
+		Some(FnArg::Receiver(syn::Receiver { reference: Some(_), mutability: None, .. })) => (quote!(check_rust_userdata), quote!(&this)),
+		Some(FnArg::Receiver(syn::Receiver { reference: Some(_), mutability: Some(_), .. })) => (quote!(check_rust_userdata_mut), quote!(&mut this)),
+		receiver => return Err(syn::Error::new(receiver.span(), "#[lua_function] methods must take `&self` or `&mut self`")),
+	};
+
+	let classic = input.sig.inputs.len() == 2 && is_state_arg(&input.sig.inputs[1]);
+	let (reads, call_args) = read_args(args)?;
+
+	let ident = &input.sig.ident;
+	let vis = &input.vis;
+	let unsafety = &input.sig.unsafety;
+	let shim = format_ident!("lua_{}", ident);
+	let doc = format!("Calls [`Self::{}`] from Lua, with the userdata as the first argument.", ident);
+
+	let push_return = if classic {
+		quote!(::gmod::lua::ValuesReturned::from(ret).into())
+	} else {
+		quote!(::gmod::lua::__LuaReturn::__push_return(ret, lua))
+	};
+
+	Ok(quote! {
+		#input
+
+		#[doc = #doc]
+		#vis #unsafety extern "C-unwind" fn #shim(lua: ::gmod::lua::State) -> i32 {
+			#[allow(unused_unsafe, unused_mut)]
+			unsafe {
+				let mut __gmod_arg = 2;
+				#(#reads)*
+
+				// The userdata is released before the return values are pushed, which can raise an error
+				let ret = {
+					let mut this = lua.#borrow::<Self>(1);
+					Self::#ident(#this, #(#call_args),*)
+				};
+				#push_return
+			}
+		}
+	})
+}
+
+/// Turns an associated function of a userdata type, which doesn't take `self`, into a `LuaFunction` named `lua_<function>`.
+///
+/// The function itself is kept as it is, like with methods. A function taking only the Lua state returns like a plain `#[lua_function]`, otherwise its arguments and return values are typed.
+fn associated_lua_function(input: ItemFn) -> Result<TokenStream, syn::Error> {
+	check_signature(&input.sig)?;
+
+	let classic = !is_typed(&input);
+	let (reads, call_args) = read_args(input.sig.inputs.iter())?;
+
+	let ident = &input.sig.ident;
+	let vis = &input.vis;
+	let unsafety = &input.sig.unsafety;
+	let shim = format_ident!("lua_{}", ident);
+	let doc = format!("Calls [`Self::{}`] from Lua.", ident);
+
+	let push_return = if classic {
+		quote!(::gmod::lua::ValuesReturned::from(ret).into())
+	} else {
+		quote!(::gmod::lua::__LuaReturn::__push_return(ret, lua))
+	};
+
+	Ok(quote! {
+		#input
+
+		#[doc = #doc]
+		#vis #unsafety extern "C-unwind" fn #shim(lua: ::gmod::lua::State) -> i32 {
+			#[allow(unused_unsafe, unused_mut)]
+			unsafe {
+				let mut __gmod_arg = 1;
+				#(#reads)*
+				let ret = Self::#ident(#(#call_args),*);
+				#push_return
+			}
+		}
+	})
+}
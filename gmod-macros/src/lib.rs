@@ -6,24 +6,60 @@ extern crate quote;
 
 use proc_macro::TokenStream;
 use quote::ToTokens;
-use syn::ItemFn;
+use syn::{spanned::Spanned, ItemFn};
+
+mod class;
+mod function;
+mod module;
 
 macro_rules! wrap_compile_error {
 	($input:ident, $code:expr) => {{
-		let orig_tokens = $input.clone();
 		match (|| -> Result<TokenStream, syn::Error> { $code })() {
 			Ok(tokens) => tokens,
-			Err(_) => return orig_tokens
+			Err(err) => err.to_compile_error().into()
 		}
 	}};
 }
 
-fn check_lua_function(input: &mut ItemFn) {
-	assert!(input.sig.asyncness.is_none(), "Cannot be async");
-	assert!(input.sig.constness.is_none(), "Cannot be const");
-	assert!(input.sig.inputs.len() == 1, "There can only be one argument, and it should be a pointer to the Lua state (gmod::lua::State)");
-	assert!(input.sig.abi.is_none() || input.sig.abi.as_ref().and_then(|abi| abi.name.as_ref()).map(|abi| abi.value() == "C-unwind").unwrap_or(true), "Do not specify an ABI");
+fn check_lua_function(input: &mut ItemFn) -> Result<(), syn::Error> {
+	if let Some(asyncness) = &input.sig.asyncness {
+		return Err(syn::Error::new(asyncness.span(), "Cannot be async"));
+	}
+	if let Some(constness) = &input.sig.constness {
+		return Err(syn::Error::new(constness.span(), "Cannot be const"));
+	}
+	if let Some(abi) = &input.sig.abi {
+		if abi.name.as_ref().map(|abi| abi.value() != "C-unwind").unwrap_or(false) {
+			return Err(syn::Error::new(abi.span(), "Do not specify an ABI"));
+		}
+	}
+	if input.sig.inputs.len() != 1 {
+		let span = if input.sig.inputs.is_empty() { input.sig.paren_token.span } else { input.sig.inputs.span() };
+		return Err(syn::Error::new(span, "There can only be one argument, and it should be a pointer to the Lua state (gmod::lua::State)"));
+	}
+	if !function::is_state_arg(&input.sig.inputs[0]) {
+		let span = match &input.sig.inputs[0] {
+			syn::FnArg::Typed(arg) => arg.ty.span(),
+			arg => arg.span(),
+		};
+		return Err(syn::Error::new(span, "The argument should be a pointer to the Lua state (gmod::lua::State)"));
+	}
 	input.sig.abi = Some(syn::parse_quote!(extern "C-unwind"));
+	Ok(())
+}
+
+/// The name of the Lua state argument of a checked function, naming it if it's a pattern such as `_`.
+fn lua_ident(input: &mut ItemFn) -> syn::Ident {
+	match &mut input.sig.inputs[0] {
+		syn::FnArg::Typed(syn::PatType { pat, .. }) => match &**pat {
+			syn::Pat::Ident(pat) => pat.ident.clone(),
+			_ => {
+				*pat = parse_quote!(__gmod_lua);
+				format_ident!("__gmod_lua")
+			}
+		},
+		_ => unreachable!(),
+	}
 }
 
 fn genericify_return(item_fn: &mut ItemFn) {
@@ -37,10 +73,10 @@ pub fn gmod13_open(_attr: TokenStream, tokens: TokenStream) -> TokenStream {
 	wrap_compile_error!(tokens, {
 		let mut input = syn::parse::<ItemFn>(tokens)?;
 
-		let lua_ident = format_ident!("{}", match &input.sig.inputs[0] {
-			syn::FnArg::Typed(arg) => arg.pat.to_token_stream().to_string(),
-			_ => unreachable!(),
-		});
+		// Make sure it's valid
+		check_lua_function(&mut input)?;
+
+		let lua_ident = lua_ident(&mut input);
 
 		// Capture the Lua state
 		input.block.stmts.insert(0, syn::parse2(quote!(::gmod::lua::__set_state__internal(#lua_ident);)).unwrap());
@@ -48,8 +84,11 @@ pub fn gmod13_open(_attr: TokenStream, tokens: TokenStream) -> TokenStream {
 		// Load lua_shared
 		input.block.stmts.insert(0, syn::parse2(quote!(#[allow(unused_unsafe)] unsafe { ::gmod::lua::load() })).unwrap());
 
-		// Make sure it's valid
-		check_lua_function(&mut input);
+		// Check the userdata type ids against the game
+		input.block.stmts.insert(2, syn::parse2(quote!(#[allow(unused_unsafe)] unsafe { ::gmod::userdata::__validate_type_ids(#lua_ident) })).unwrap());
+
+		// Register every #[lua_export]
+		input.block.stmts.insert(3, syn::parse2(quote!(#[allow(unused_unsafe)] unsafe { ::gmod::lua::__register_exports(#lua_ident) })).unwrap());
 
 		// No mangling
 		input.attrs.push(parse_quote!(#[no_mangle]));
@@ -67,11 +106,24 @@ pub fn gmod13_close(_attr: TokenStream, tokens: TokenStream) -> TokenStream {
 		let mut input = syn::parse::<ItemFn>(tokens)?;
 
 		// Make sure it's valid
-		check_lua_function(&mut input);
+		check_lua_function(&mut input)?;
+
+		// We need the Lua state even if the function ignores it
+		let lua_ident = lua_ident(&mut input);
 
 		// No mangling
 		input.attrs.push(parse_quote!(#[no_mangle]));
 
+		// Drop leftover userdata and stop Lua calling into the module once it's unmapped
+		let stmts = std::mem::take(&mut input.block.stmts);
+		let output = &input.sig.output;
+		input.block.stmts = vec![syn::parse2(quote!({
+			let ret = (|| #output {#(#stmts);*})();
+			#[allow(unused_unsafe)]
+			unsafe { ::gmod::lua::__unload(#lua_ident) }
+			ret
+		})).unwrap()];
+
 		// Shutdown gmcl thread if it's running
 		#[cfg(feature = "gmcl")] {
 			let stmts = std::mem::take(&mut input.block.stmts);
@@ -89,17 +141,148 @@ pub fn gmod13_close(_attr: TokenStream, tokens: TokenStream) -> TokenStream {
 	})
 }
 
+/// Makes a function callable from Lua.
+///
+/// A function taking only the Lua state is exported as is, and returns the number of values it pushed (or anything else a `ValuesReturned` can be made from, such as `Result<i32, E>`).
+///
+/// Any other function is typed: its arguments are read from the Lua stack with `FromLuaMulti`, raising a "bad argument" error if they can't be converted, and its return value is pushed with `PushToLuaMulti`. A `Result` return value raises its error. The Lua state can still be taken as the first argument. Optional arguments can be taken as `Option<T>`, any value as `AnyValue`, and the rest of the arguments as `Variadic<T>`.
+///
+/// In an `impl` block, a method taking `&self` or `&mut self` is kept as it is, and a `lua_<method>` function is generated which borrows `self` from a Rust userdata in argument 1 (raising an error if it's the wrong type or already borrowed) and reads the rest of the arguments from argument 2 onwards.
+///
+/// The macro can't see the `impl` block around a function, so associated functions that don't take `self` need `#[lua_function(associated)]` to be kept as they are too, with a `lua_<function>` function generated for Lua. Without it they're turned into a Lua function in place, like a free function. Inside a `#[lua_methods]` block, `associated` is implied.
+///
+/// ```ignore
+/// #[lua_function]
+/// fn clamp(x: f64, min: Option<f64>, max: Option<f64>) -> f64 {
+///     x.max(min.unwrap_or(f64::MIN)).min(max.unwrap_or(f64::MAX))
+/// }
+///
+/// impl Counter {
+///     #[lua_function(associated)]
+///     fn new(count: i32) -> Self {
+///         Counter { count }
+///     }
+///
+///     #[lua_function]
+///     fn add(&mut self, n: i32) -> i32 {
+///         self.count += n;
+///         self.count
+///     }
+/// }
+///
+/// lua.new_userdata(Counter::new(0), None);
+/// lua.push_function(Counter::lua_add);
+/// lua.push_function(Counter::lua_new);
+/// ```
 #[proc_macro_attribute]
-pub fn lua_function(_attr: TokenStream, tokens: TokenStream) -> TokenStream {
+pub fn lua_function(attr: TokenStream, tokens: TokenStream) -> TokenStream {
 	wrap_compile_error!(tokens, {
-		let mut input = syn::parse::<ItemFn>(tokens)?;
+		let args = syn::parse::<function::FunctionArgs>(attr)?;
+		let input = syn::parse::<ItemFn>(tokens)?;
+		Ok(function::lua_function(args, input)?.into())
+	})
+}
 
-		// Make sure it's valid
-		check_lua_function(&mut input);
+/// A `#[lua_function]` which `#[gmod13_open]` registers automatically, at the given dotted path from `_G`.
+///
+/// Any tables along the path that don't exist yet are created, so exports can be spread across the crate without a central registration function.
+///
+/// Each path can only be exported once: if several functions are exported at the same path, none of them are registered and an error is printed to the console.
+///
+/// ```ignore
+/// #[lua_export("mymod.strings.upper")]
+/// fn upper(s: String) -> String {
+///     s.to_uppercase()
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn lua_export(attr: TokenStream, tokens: TokenStream) -> TokenStream {
+	wrap_compile_error!(tokens, {
+		let path = syn::parse::<syn::LitStr>(attr)?;
+		let input = syn::parse::<ItemFn>(tokens)?;
+		Ok(function::lua_export(path, input)?.into())
+	})
+}
 
-		// Make the return type nice and dynamic
-		genericify_return(&mut input);
+/// Implements `gmod::userdata::LuaUserData` for a struct, so it can be pushed to Lua with `State::push_userdata`.
+///
+/// The type is named after the struct in Lua, or `#[lua_class(name = "...")]`. Its metatable's registry key also includes the module's address, so other gmod-rs modules can have a type of the same name. Fields marked with `#[lua(get)]` and/or `#[lua(set)]` are exposed to Lua, optionally renamed with `#[lua(name = "...")]`.
+///
+/// Metamethods can be derived from traits the type implements with `meta(...)`:
+///
+/// * `Display` sets `__tostring`
+/// * `PartialEq` sets `__eq`
+/// * `PartialOrd` sets `__lt` and `__le`
+/// * `Add`, `Sub`, `Mul` and `Div` set `__add`, `__sub`, `__mul` and `__div`. Give a right hand side type, like `Mul<f64>`, to take something other than `Self`. The same trait can be given with several right hand side types, like `Mul, Mul<f64>`, and the metamethod uses whichever matches the operands
+/// * `Neg` sets `__unm`
+/// * `gmod::userdata::meta::LuaLen` sets `__len`
+///
+/// `call = ...` sets `__call` to a closure or function taking `&mut Self` and the Lua state, which returns like a `#[lua_function]`.
+///
+/// ```ignore
+/// #[lua_class(name = "Vec3", meta(Display, PartialEq, Add, Mul, Mul<f64>, Neg), call = |this, lua| { lua.push_number(this.x); 1 })]
+/// #[derive(Clone, PartialEq)]
+/// struct Vec3 { x: f64, y: f64, z: f64 }
+/// ```
+#[proc_macro_attribute]
+pub fn lua_class(attr: TokenStream, tokens: TokenStream) -> TokenStream {
+	wrap_compile_error!(tokens, {
+		let args = syn::parse::<class::ClassArgs>(attr)?;
+		let input = syn::parse::<syn::ItemStruct>(tokens)?;
+		Ok(class::lua_class(args, input)?.into())
+	})
+}
 
-		Ok(input.into_token_stream().into())
+/// Exposes every method in this `impl` block to Lua as a method of the `#[lua_class]` type.
+///
+/// Methods take `&self` or `&mut self` and the Lua state, and are called with the userdata as the first argument. Use `#[lua(name = "...")]` to rename a method (names starting with `__` become metamethods) and `#[lua(skip)]` to keep it out of Lua.
+///
+/// Methods and associated functions marked `#[lua_function]` can take and return typed values instead, and are registered through the `lua_<name>` function it generates.
+#[proc_macro_attribute]
+pub fn lua_methods(_attr: TokenStream, tokens: TokenStream) -> TokenStream {
+	wrap_compile_error!(tokens, {
+		let input = syn::parse::<syn::ItemImpl>(tokens)?;
+		Ok(class::lua_methods(input)?.into())
+	})
+}
+
+/// Exports the contents of a module to Lua as a global table.
+///
+/// Every `#[lua_function]`, `pub const` and `pub mod` (which becomes a nested table, and must be inline) in the module is exported under its own name, or `#[lua(name = "...")]`. Use `#[lua(skip)]` to keep something out of Lua.
+///
+/// `#[lua_function]`s are exported whatever their visibility, as the attribute already opts them in, while constants and modules opt in by being `pub`. A private module containing `#[lua_function]`s is an error unless it's `#[lua(skip)]`ped.
+///
+/// The table is named after the module, or `#[lua_module(name = "...")]`, and is also put in `package.loaded` with `#[lua_module(loaded)]`, so that `require` finds it. It's registered by calling the generated `register` function:
+///
+/// ```ignore
+/// #[lua_module(name = "mymod")]
+/// mod mymod {
+///     pub const VERSION: &str = "1.0.0";
+///
+///     #[lua_function]
+///     fn add(a: f64, b: f64) -> f64 {
+///         a + b
+///     }
+///
+///     pub mod strings {
+///         #[lua_function]
+///         fn upper(s: String) -> String {
+///             s.to_uppercase()
+///         }
+///     }
+/// }
+///
+/// #[gmod13_open]
+/// unsafe fn gmod13_open(lua: gmod::lua::State) -> i32 {
+///     mymod::register(lua);
+///     0
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn lua_module(attr: TokenStream, tokens: TokenStream) -> TokenStream {
+	wrap_compile_error!(tokens, {
+		let args = syn::parse::<module::ModuleArgs>(attr)?;
+		let input = syn::parse::<syn::ItemMod>(tokens)?;
+		Ok(module::lua_module(args, input)?.into())
 	})
-}
\ No newline at end of file
+}
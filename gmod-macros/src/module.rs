@@ -0,0 +1,148 @@
+use proc_macro2::TokenStream;
+use quote::ToTokens;
+use syn::{ext::IdentExt, parse::Parse, spanned::Spanned, Item, ItemMod, LitStr, Visibility};
+
+use crate::class::take_lua_attrs;
+
+/// Arguments of `#[lua_module(...)]`
+#[derive(Default)]
+pub struct ModuleArgs {
+	name: Option<LitStr>,
+	loaded: bool,
+}
+impl Parse for ModuleArgs {
+	fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+		let mut args = ModuleArgs::default();
+		while !input.is_empty() {
+			let ident: syn::Ident = input.parse()?;
+			if ident == "name" {
+				input.parse::<Token![=]>()?;
+				args.name = Some(input.parse()?);
+			} else if ident == "loaded" {
+				args.loaded = true;
+			} else {
+				return Err(syn::Error::new(ident.span(), "unknown option, expected `name = \"...\"` or `loaded`"));
+			}
+			if !input.is_empty() {
+				input.parse::<Token![,]>()?;
+			}
+		}
+		Ok(args)
+	}
+}
+
+fn has_attr(attrs: &[syn::Attribute], name: &str) -> bool {
+	attrs.iter().any(|attr| attr.path.segments.last().map(|segment| segment.ident == name).unwrap_or(false))
+}
+
+/// The name of an exported item in Lua, from `#[lua(name = "...")]` or its identifier. `None` if it's `#[lua(skip)]`ped.
+fn lua_name(attrs: &mut Vec<syn::Attribute>, ident: &syn::Ident) -> Result<Option<LitStr>, syn::Error> {
+	let lua_attr = take_lua_attrs(attrs)?;
+	if lua_attr.get || lua_attr.set {
+		return Err(syn::Error::new(ident.span(), "`get` and `set` are only for #[lua_class] fields"));
+	}
+	if lua_attr.skip {
+		return Ok(None);
+	}
+	Ok(Some(match lua_attr.name {
+		Some(name) => LitStr::new(&name, ident.span()),
+		None => LitStr::new(&ident.unraw().to_string(), ident.span()),
+	}))
+}
+
+/// Whether a module has any `#[lua_function]`s, including in its submodules.
+fn has_lua_functions(module: &ItemMod) -> bool {
+	let items = match &module.content {
+		Some((_, items)) => items,
+		None => return false,
+	};
+	items.iter().any(|item| match item {
+		Item::Fn(func) => has_attr(&func.attrs, "lua_function"),
+		Item::Mod(submodule) => has_lua_functions(submodule),
+		_ => false,
+	})
+}
+
+/// Adds `__gmod_push_module` to a module, which pushes a table of its exports.
+fn build_module(module: &mut ItemMod) -> Result<(), syn::Error> {
+	let items = match &mut module.content {
+		Some((_, items)) => items,
+		None => return Err(syn::Error::new(module.span(), "#[lua_module] modules must be inline")),
+	};
+
+	let mut fields = Vec::new();
+	for item in items.iter_mut() {
+		match item {
+			Item::Fn(func) if has_attr(&func.attrs, "lua_function") => {
+				if let Some(name) = lua_name(&mut func.attrs, &func.sig.ident)? {
+					let ident = &func.sig.ident;
+					fields.push(quote!(lua.push_function(#ident); lua.set_field(-2, ::gmod::lua_string!(#name));));
+				}
+			},
+			Item::Const(item) if !matches!(item.vis, Visibility::Inherited) => {
+				if let Some(name) = lua_name(&mut item.attrs, &item.ident)? {
+					let ident = &item.ident;
+					fields.push(quote!(::gmod::lua::PushToLua::push_to_lua(#ident, lua); lua.set_field(-2, ::gmod::lua_string!(#name));));
+				}
+			},
+			Item::Mod(submodule) if !matches!(submodule.vis, Visibility::Inherited) => {
+				if let Some(name) = lua_name(&mut submodule.attrs, &submodule.ident)? {
+					build_module(submodule)?;
+					let ident = &submodule.ident;
+					fields.push(quote!(#ident::__gmod_push_module(lua); lua.set_field(-2, ::gmod::lua_string!(#name));));
+				}
+			},
+			Item::Mod(submodule) => {
+				// #[lua_function]s are exported whatever their visibility, so it'd be surprising for a private module to hide them
+				if lua_name(&mut submodule.attrs, &submodule.ident)?.is_some() && has_lua_functions(submodule) {
+					return Err(syn::Error::new(submodule.ident.span(), "private modules aren't exported, but this one has #[lua_function]s: make it `pub`, or add `#[lua(skip)]` to keep it out of Lua"));
+				}
+			},
+			_ => {},
+		}
+	}
+
+	let n = fields.len() as i32;
+	items.push(syn::parse2(quote! {
+		#[doc(hidden)]
+		#[allow(unused_unsafe)]
+		pub(super) unsafe fn __gmod_push_module(lua: ::gmod::lua::State) {
+			lua.create_table(0, #n);
+			lua.neutralize_on_unload(-1);
+			#(#fields)*
+		}
+	})?);
+
+	Ok(())
+}
+
+pub fn lua_module(args: ModuleArgs, mut module: ItemMod) -> Result<TokenStream, syn::Error> {
+	build_module(&mut module)?;
+
+	let name = args.name.unwrap_or_else(|| LitStr::new(&module.ident.unraw().to_string(), module.ident.span()));
+	let loaded = if args.loaded {
+		quote! {
+			lua.get_global(::gmod::lua_string!("package"));
+			lua.get_field(-1, ::gmod::lua_string!("loaded"));
+			lua.push_value(-3);
+			lua.set_field(-2, ::gmod::lua_string!(#name));
+			lua.pop_n(2);
+		}
+	} else {
+		quote!()
+	};
+
+	let doc = format!("Registers this module as the global table `{}`.", name.value());
+	let (_, items) = module.content.as_mut().unwrap();
+	items.push(syn::parse2(quote! {
+		#[doc = #doc]
+		#[allow(clippy::missing_safety_doc)]
+		pub unsafe fn register(lua: ::gmod::lua::State) {
+			__gmod_push_module(lua);
+			#loaded
+			lua.set_global(::gmod::lua_string!(#name));
+		}
+	})?);
+
+	Ok(module.into_token_stream())
+}
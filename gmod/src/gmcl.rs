@@ -53,7 +53,7 @@ pub fn override_stdout() {
 					data.push(0); // cheeky
 					ConMsg(data.as_ptr() as *const i8);
 
-					data.truncate(0);
+					data.clear();
 				},
 				Err(TryLockError::Poisoned(err)) => panic!("{}", err),
 				Err(TryLockError::WouldBlock) => {
@@ -14,6 +14,9 @@ pub use cstr;
 pub use libloading;
 pub use gmod_macros::*;
 
+#[doc(hidden)]
+pub use linkme;
+
 #[cfg(feature = "hax")]
 mod haxports {
 	#[cfg(not(target_os = "macos"))]
@@ -104,7 +107,7 @@ pub fn is_x86_64() -> bool {
 /// # Example
 /// ```no_run
 /// // This would only work on Windows x86-64 branch in 64-bit mode
-/// let (engine, engine_path): (gmod::libloading::Library, &'static str) = open_library_srv!("bin/win64/engine.dll").expect("Failed to open engine.dll!");
+/// let (engine, engine_path): (gmod::libloading::Library, &'static str) = unsafe { gmod::open_library_raw!("bin/win64/engine.dll") }.expect("Failed to open engine.dll!");
 /// println!("Opened engine.dll from: {}", engine_path);
 /// ```
 #[macro_export]
@@ -123,7 +126,7 @@ macro_rules! open_library_raw {
 ///
 /// # Example
 /// ```no_run
-/// let (engine, engine_path): (gmod::libloading::Library, &'static str) = open_library_srv!("engine").expect("Failed to open engine.dll!");
+/// let (engine, engine_path): (gmod::libloading::Library, &'static str) = unsafe { gmod::open_library_srv!("engine") }.expect("Failed to open engine.dll!");
 /// println!("Opened engine.dll from: {}", engine_path);
 /// ```
 #[macro_export]
@@ -190,7 +193,7 @@ macro_rules! open_library_srv {
 ///
 /// # Example
 /// ```no_run
-/// let (engine, engine_path): (gmod::libloading::Library, &'static str) = open_library!("engine").expect("Failed to open engine.dll!");
+/// let (engine, engine_path): (gmod::libloading::Library, &'static str) = unsafe { gmod::open_library!("engine") }.expect("Failed to open engine.dll!");
 /// println!("Opened engine.dll from: {}", engine_path);
 /// ```
 #[macro_export]
@@ -0,0 +1,120 @@
+use std::ffi::CStr;
+
+use crate::lua::*;
+
+/// A single level of a Lua call stack, with its debug information copied out of the `LuaDebug` record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+	name: Option<String>,
+	namewhat: String,
+	what: String,
+	source: String,
+	short_src: String,
+	currentline: i32,
+	linedefined: i32,
+}
+impl Frame {
+	unsafe fn from_lua_debug(ar: &LuaDebug) -> Frame {
+		unsafe fn to_string(ptr: LuaString) -> Option<String> {
+			if ptr.is_null() {
+				None
+			} else {
+				Some(CStr::from_ptr(ptr).to_string_lossy().into_owned())
+			}
+		}
+
+		Frame {
+			name: to_string(ar.name),
+			namewhat: to_string(ar.namewhat).unwrap_or_default(),
+			what: to_string(ar.what).unwrap_or_default(),
+			source: to_string(ar.source).unwrap_or_default(),
+			short_src: to_string(ar.short_src.as_ptr()).unwrap_or_default(),
+			currentline: ar.currentline,
+			linedefined: ar.linedefined,
+		}
+	}
+
+	#[inline]
+	/// A reasonable name for the function, if Lua could find one.
+	pub fn name(&self) -> Option<&str> {
+		self.name.as_deref()
+	}
+
+	#[inline]
+	/// Explains the `name` field: `"global"`, `"local"`, `"method"`, `"field"`, `"upvalue"` or `""`.
+	pub fn namewhat(&self) -> &str {
+		&self.namewhat
+	}
+
+	#[inline]
+	/// `"Lua"`, `"C"`, `"main"` or `"tail"`.
+	pub fn what(&self) -> &str {
+		&self.what
+	}
+
+	#[inline]
+	/// Where the function was defined, such as `"@lua/autorun/foo.lua"`.
+	pub fn source(&self) -> &str {
+		&self.source
+	}
+
+	#[inline]
+	/// A printable version of `source`, as used in error messages.
+	pub fn short_src(&self) -> &str {
+		&self.short_src
+	}
+
+	#[inline]
+	/// The line currently being executed, or `-1` if unavailable.
+	pub fn currentline(&self) -> i32 {
+		self.currentline
+	}
+
+	#[inline]
+	/// The line where the function's definition starts.
+	pub fn linedefined(&self) -> i32 {
+		self.linedefined
+	}
+}
+impl std::fmt::Display for Frame {
+	/// Formats this frame as a line of `debug.traceback`, without the leading tab.
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}:", self.short_src)?;
+		if self.currentline > 0 {
+			write!(f, "{}:", self.currentline)?;
+		}
+		if !self.namewhat.is_empty() {
+			write!(f, " in function '{}'", self.name.as_deref().unwrap_or("?"))
+		} else {
+			match self.what.as_bytes().first() {
+				Some(b'm') => f.write_str(" in main chunk"),
+				Some(b'C' | b't') => f.write_str(" ?"),
+				_ => write!(f, " in function <{}:{}>", self.short_src, self.linedefined),
+			}
+		}
+	}
+}
+
+impl State {
+	/// Walks the call stack, starting at the currently running function, and returns up to `max_depth` frames.
+	pub unsafe fn backtrace(&self, max_depth: usize) -> Vec<Frame> {
+		let mut frames = Vec::new();
+		for level in 0..max_depth.min(i32::MAX as usize) as i32 {
+			match self.debug_getinfo_at(level, crate::lua_string!("nSl")) {
+				Some(ar) => frames.push(Frame::from_lua_debug(&ar)),
+				None => break,
+			}
+		}
+		frames
+	}
+
+	/// Returns up to `max_depth` frames of the call stack, formatted like `debug.traceback`.
+	pub unsafe fn traceback(&self, max_depth: usize) -> String {
+		let mut traceback = String::from("stack traceback:");
+		for frame in self.backtrace(max_depth) {
+			traceback.push_str("\n\t");
+			traceback.push_str(&frame.to_string());
+		}
+		traceback
+	}
+}
@@ -0,0 +1,94 @@
+use crate::lua::{FromLuaMulti, State, ValuesReturned, __LuaReturn};
+
+/// Calls the closure in upvalue 1 of the running C closure.
+unsafe extern "C-unwind" fn call_rust_closure<F, R>(lua: State) -> i32
+where
+	F: FnMut(State) -> R + 'static,
+	R: Into<ValuesReturned>,
+{
+	let ret = match lua.try_rust_userdata_mut::<F>(lua.upvalue_index(1)) {
+		Ok(mut f) => f(lua),
+		Err(err) => lua.error(format!("can't call Rust closure: {}", err)),
+	};
+
+	// The closure is released by now, so raising an error here can't leave it borrowed
+	ret.into().0
+}
+
+/// Reads the arguments of the typed closure in upvalue 1 of the running C closure, then calls it.
+unsafe extern "C-unwind" fn call_typed_closure<F, A, R>(lua: State) -> i32
+where
+	F: FnMut(A) -> R + 'static,
+	A: FromLuaMulti,
+	R: __LuaReturn,
+{
+	// Read the arguments first, so that raising an error for them can't leave the closure borrowed
+	let args = match A::from_lua_multi(lua, 1) {
+		Ok(args) => args,
+		Err(err) => lua.arg_error(err.index, err.to_string()),
+	};
+
+	let ret = match lua.try_rust_userdata_mut::<F>(lua.upvalue_index(1)) {
+		Ok(mut f) => f(args),
+		Err(err) => lua.error(format!("can't call Rust closure: {}", err)),
+	};
+
+	// The closure is released by now, so raising an error for a returned `Err` can't leave it borrowed
+	ret.__push_return(lua)
+}
+
+impl State {
+	/// Pushes a Rust closure onto the stack as a Lua function.
+	///
+	/// The closure is moved into a userdata which is kept as the function's only upvalue, and dropped by its `__gc` metamethod once the function is garbage collected. It returns like a `#[lua_function]`.
+	///
+	/// The function Lua sees is a small Lua wrapper around the C closure, which is cut off from it when the module is unloaded, so calling it afterwards raises a Lua error wherever it's been stored.
+	///
+	/// The closure can't be called again while it's running (for example if it calls a Lua function which calls it back), and raises a Lua error if it is.
+	///
+	/// ## Example
+	///
+	/// ```ignore
+	/// let mut calls = 0;
+	/// lua.push_rust_closure(move |lua| {
+	///     calls += 1;
+	///     lua.push_integer(calls);
+	///     1
+	/// });
+	/// lua.set_global(lua_string!("count_calls"));
+	/// ```
+	pub unsafe fn push_rust_closure<F, R>(&self, f: F)
+	where
+		F: FnMut(State) -> R + 'static,
+		R: Into<ValuesReturned>,
+	{
+		self.new_userdata(f, None);
+		self.push_closure(call_rust_closure::<F, R>, 1);
+		crate::lua::wrap_function(*self);
+	}
+
+	/// Like `push_rust_closure`, but the closure's arguments are read with `FromLuaMulti` and its return values are pushed with `PushToLuaMulti`, like a typed `#[lua_function]`.
+	///
+	/// Raises a Lua error if the arguments can't be converted. The closure can also return a `Result`, whose error is raised as a Lua error.
+	///
+	/// ## Example
+	///
+	/// ```ignore
+	/// let scale = 2.0;
+	/// lua.push_typed_closure(move |(x, y): (f64, f64)| (x * scale, y * scale));
+	/// lua.set_global(lua_string!("scale"));
+	///
+	/// lua.push_typed_closure(|path: String| std::fs::read_to_string(path));
+	/// lua.set_global(lua_string!("read_file"));
+	/// ```
+	pub unsafe fn push_typed_closure<F, A, R>(&self, f: F)
+	where
+		F: FnMut(A) -> R + 'static,
+		A: FromLuaMulti,
+		R: __LuaReturn,
+	{
+		self.new_userdata(f, None);
+		self.push_closure(call_typed_closure::<F, A, R>, 1);
+		crate::lua::wrap_function(*self);
+	}
+}
@@ -0,0 +1,160 @@
+use std::{ffi::{c_void, CStr}, fmt::{self, Write}};
+
+use crate::lua::*;
+
+/// How many levels of nested tables `dump_stack` and `dump_val` will print.
+pub const DUMP_DEFAULT_DEPTH: usize = 2;
+
+/// Adapts an `io::Write` into a `fmt::Write`, remembering the underlying I/O error.
+struct IoWriter<'a, W: std::io::Write> {
+	inner: &'a mut W,
+	error: Option<std::io::Error>,
+}
+impl<W: std::io::Write> fmt::Write for IoWriter<'_, W> {
+	fn write_str(&mut self, s: &str) -> fmt::Result {
+		self.inner.write_all(s.as_bytes()).map_err(|err| {
+			self.error = Some(err);
+			fmt::Error
+		})
+	}
+}
+impl<W: std::io::Write> IoWriter<'_, W> {
+	fn finish(self, result: fmt::Result) -> std::io::Result<()> {
+		match (result, self.error) {
+			(Ok(_), _) => Ok(()),
+			(Err(_), Some(err)) => Err(err),
+			(Err(err), None) => Err(std::io::Error::other(err)),
+		}
+	}
+}
+
+impl State {
+	/// Prints the contents of the stack to stdout, recursing `DUMP_DEFAULT_DEPTH` levels into tables.
+	///
+	/// Use `dump_stack_fmt` or `dump_stack_io` to write it somewhere else.
+	pub unsafe fn dump_stack(&self) {
+		let mut stdout = std::io::stdout().lock();
+		let _ = self.dump_stack_io(&mut stdout, DUMP_DEFAULT_DEPTH);
+	}
+
+	/// Writes the contents of the stack into `w`, recursing `max_depth` levels into tables.
+	pub unsafe fn dump_stack_fmt<W: Write>(&self, w: &mut W, max_depth: usize) -> fmt::Result {
+		let top = self.get_top();
+		writeln!(w, "\n=== STACK DUMP ===")?;
+		writeln!(w, "Stack size: {}", top)?;
+		for i in 1..=top {
+			write!(w, "{}. ", i)?;
+			self.write_val(w, i, max_depth, 0, &mut Vec::new())?;
+			writeln!(w)?;
+		}
+		writeln!(w)
+	}
+
+	/// Writes the contents of the stack into `w`, recursing `max_depth` levels into tables.
+	pub unsafe fn dump_stack_io<W: std::io::Write>(&self, w: &mut W, max_depth: usize) -> std::io::Result<()> {
+		let mut w = IoWriter { inner: w, error: None };
+		let result = self.dump_stack_fmt(&mut w, max_depth);
+		w.finish(result)
+	}
+
+	/// Returns a human readable representation of the value at the given index, recursing `DUMP_DEFAULT_DEPTH` levels into tables.
+	pub unsafe fn dump_val(&self, index: i32) -> String {
+		let mut out = String::new();
+		let _ = self.dump_val_fmt(&mut out, index, DUMP_DEFAULT_DEPTH);
+		out
+	}
+
+	/// Writes a human readable representation of the value at the given index into `w`, recursing `max_depth` levels into tables.
+	///
+	/// Tables that have already been printed further up are shown as `<cycle>` rather than recursed into again.
+	pub unsafe fn dump_val_fmt<W: Write>(&self, w: &mut W, index: i32, max_depth: usize) -> fmt::Result {
+		self.write_val(w, self.abs_index(index), max_depth, 0, &mut Vec::new())
+	}
+
+	/// See `dump_val_fmt`
+	pub unsafe fn dump_val_io<W: std::io::Write>(&self, w: &mut W, index: i32, max_depth: usize) -> std::io::Result<()> {
+		let mut w = IoWriter { inner: w, error: None };
+		let result = self.dump_val_fmt(&mut w, index, max_depth);
+		w.finish(result)
+	}
+
+	unsafe fn write_val<W: Write>(&self, w: &mut W, index: i32, depth: usize, indent: usize, seen: &mut Vec<*const c_void>) -> fmt::Result {
+		match self.type_of(index) {
+			LuaType::None => w.write_str("none"),
+			LuaType::Nil => w.write_str("nil"),
+			LuaType::Boolean => write!(w, "{}", self.get_boolean(index)),
+			LuaType::Number => write!(w, "{}", self.to_number(index)),
+			LuaType::String => write!(w, "{:?}", self.get_string(index).unwrap_or_default()),
+			LuaType::Table => self.write_table(w, index, depth, indent, seen),
+			LuaType::Function => {
+				write!(w, "function: {:p}", self.to_pointer(index))?;
+
+				self.push_value(index);
+				match self.debug_getinfo_from_stack(crate::lua_string!(">S")) {
+					Some(ar) if !ar.what.is_null() && CStr::from_ptr(ar.what).to_bytes() == b"C" => w.write_str(" [C]"),
+					Some(ar) => write!(w, " ({}:{})", CStr::from_ptr(ar.short_src.as_ptr()).to_string_lossy(), ar.linedefined),
+					None => Ok(()),
+				}
+			},
+			LuaType::UserData => match super::value::read_tagged_userdata(*self, index) {
+				Some(tagged) => write!(w, "{:?}: {:p}", tagged.resolved_type(), self.to_pointer(index)),
+				None => write!(w, "userdata: {:p}", self.to_pointer(index)),
+			},
+			LuaType::LightUserData => write!(w, "lightuserdata: {:p}", self.to_userdata(index)),
+			LuaType::Thread => write!(w, "thread: {:p}", self.to_pointer(index)),
+		}
+	}
+
+	unsafe fn write_table<W: Write>(&self, w: &mut W, index: i32, depth: usize, indent: usize, seen: &mut Vec<*const c_void>) -> fmt::Result {
+		let ptr = self.to_pointer(index);
+		write!(w, "table: {:p}", ptr)?;
+
+		if seen.contains(&ptr) {
+			return w.write_str(" <cycle>");
+		}
+		if depth == 0 || !self.check_stack(3) {
+			return Ok(());
+		}
+
+		seen.push(ptr);
+
+		w.write_str(" {")?;
+		let mut empty = true;
+		self.push_nil();
+		while self.next(index) != 0 {
+			let result = self.write_table_entry(w, empty, depth, indent, seen);
+			if result.is_err() {
+				self.pop_n(2);
+				seen.pop();
+				return result;
+			}
+			empty = false;
+			self.pop();
+		}
+		if !empty {
+			for _ in 0..indent {
+				w.write_char('\t')?;
+			}
+		}
+
+		seen.pop();
+
+		w.write_char('}')
+	}
+
+	/// Writes the key-value pair at the top of the stack, as left by `lua_next`.
+	unsafe fn write_table_entry<W: Write>(&self, w: &mut W, first: bool, depth: usize, indent: usize, seen: &mut Vec<*const c_void>) -> fmt::Result {
+		let top = self.get_top();
+		if first {
+			writeln!(w)?;
+		}
+		for _ in 0..=indent {
+			w.write_char('\t')?;
+		}
+		w.write_char('[')?;
+		self.write_val(w, top - 1, 0, indent + 1, seen)?;
+		w.write_str("] = ")?;
+		self.write_val(w, top, depth - 1, indent + 1, seen)?;
+		writeln!(w, ",")
+	}
+}
@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+
+use crate::lua::{LuaFunction, State, LUA_GLOBALSINDEX};
+
+#[doc(hidden)]
+/// A function exported with `#[lua_export]`.
+pub struct LuaExport {
+	/// Dotted path from `_G`, such as `mymod.sub.func`
+	pub path: &'static str,
+	pub func: LuaFunction,
+}
+
+#[doc(hidden)]
+/// Every function exported with `#[lua_export]` in the binary, collected at link time.
+#[linkme::distributed_slice]
+pub static __LUA_EXPORTS: [LuaExport] = [..];
+
+/// Sets `path` in `_G` to `func`, creating any missing tables along the way.
+///
+/// Only the function itself is neutralized on unload, as the tables along the way could be shared with other modules, even if we created them.
+unsafe fn register(lua: State, export: &LuaExport) {
+	let mut segments = export.path.split('.');
+	let name = segments.next_back().unwrap();
+
+	lua.push_value(LUA_GLOBALSINDEX);
+	for segment in segments {
+		lua.push_string(segment);
+		lua.raw_get(-2);
+		if lua.is_nil(-1) {
+			lua.pop();
+			lua.new_table();
+			lua.push_string(segment);
+			lua.push_value(-2);
+			lua.raw_set(-4);
+		} else if !lua.is_table(-1) {
+			let got = lua.get_type(-1);
+			lua.pop_n(2);
+			eprintln!("[gmod-rs] can't export {}: {} is a {}, not a table", export.path, segment, got);
+			return;
+		}
+		lua.remove(-2);
+	}
+
+	lua.push_string(name);
+	lua.push_function(export.func);
+	lua.raw_set(-3);
+	lua.neutralize_field_on_unload(-1, name);
+	lua.pop();
+}
+
+#[doc(hidden)]
+/// Called by `#[gmod13_open]`
+///
+/// A path exported more than once isn't registered at all, as which export would win depends on the order they were linked in.
+pub unsafe fn __register_exports(lua: State) {
+	let mut counts = HashMap::new();
+	for export in __LUA_EXPORTS {
+		*counts.entry(export.path).or_insert(0) += 1;
+	}
+
+	for export in __LUA_EXPORTS {
+		let count = counts.get_mut(export.path).unwrap();
+		match *count {
+			1 => register(lua, export),
+
+			// Already reported
+			0 => {},
+
+			_ => {
+				eprintln!("[gmod-rs] can't export {}: it's exported by {} functions", export.path, count);
+				*count = 0;
+			}
+		}
+	}
+}
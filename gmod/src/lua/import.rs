@@ -175,6 +175,14 @@ pub struct LuaShared {
 	pub lua_status: Symbol<'static, unsafe extern "C-unwind" fn(state: LuaState) -> i32>,
 	pub lua_xmove: Symbol<'static, unsafe extern "C-unwind" fn(thread1: LuaState, thread2: LuaState, n: i32)>,
 	pub lua_equal: Symbol<'static, unsafe extern "C-unwind" fn(state: LuaState, index1: i32, index2: i32) -> i32>,
+	pub lua_checkstack: Symbol<'static, unsafe extern "C-unwind" fn(state: LuaState, extra: i32) -> i32>,
+	pub lual_argerror: Symbol<'static, unsafe extern "C-unwind" fn(state: LuaState, arg: i32, msg: LuaString) -> i32>,
+	pub lua_getfenv: Symbol<'static, unsafe extern "C-unwind" fn(state: LuaState, index: i32)>,
+	pub lua_setfenv: Symbol<'static, unsafe extern "C-unwind" fn(state: LuaState, index: i32) -> i32>,
+	pub lua_rawget: Symbol<'static, unsafe extern "C-unwind" fn(state: LuaState, index: i32)>,
+	pub lua_rawset: Symbol<'static, unsafe extern "C-unwind" fn(state: LuaState, index: i32)>,
+	pub lua_iscfunction: Symbol<'static, unsafe extern "C-unwind" fn(state: LuaState, index: i32) -> i32>,
+	pub lua_setupvalue: Symbol<'static, unsafe extern "C-unwind" fn(state: LuaState, funcindex: i32, n: i32) -> LuaString>,
 }
 unsafe impl Sync for LuaShared {}
 impl LuaShared {
@@ -250,6 +258,14 @@ impl LuaShared {
 				lua_status: find_symbol!("lua_status"),
 				lua_xmove: find_symbol!("lua_xmove"),
 				lua_equal: find_symbol!("lua_equal"),
+				lua_checkstack: find_symbol!("lua_checkstack"),
+				lual_argerror: find_symbol!("luaL_argerror"),
+				lua_getfenv: find_symbol!("lua_getfenv"),
+				lua_setfenv: find_symbol!("lua_setfenv"),
+				lua_rawget: find_symbol!("lua_rawget"),
+				lua_rawset: find_symbol!("lua_rawset"),
+				lua_iscfunction: find_symbol!("lua_iscfunction"),
+				lua_setupvalue: find_symbol!("lua_setupvalue"),
 				library,
 			}
 		}
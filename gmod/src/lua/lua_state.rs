@@ -99,11 +99,23 @@ impl LuaState {
 	}
 
 	/// Returns the name of the type of the value at the given index.
-	pub unsafe fn get_type(&self, index: i32) -> &str {
-		let lua_type = (LUA_SHARED.lua_type)(*self, index);
-		let lua_type_str_ptr = (LUA_SHARED.lua_typename)(*self, lua_type);
-		let lua_type_str = std::ffi::CStr::from_ptr(lua_type_str_ptr);
-		unsafe { std::str::from_utf8_unchecked(lua_type_str.to_bytes()) }
+	#[inline(always)]
+	pub unsafe fn get_type(&self, index: i32) -> &'static str {
+		self.type_of(index).name()
+	}
+
+	/// Returns the type of the value at the given index.
+	///
+	/// LuaJIT's `cdata` (only reachable if the FFI library is exposed) is reported as `LuaType::UserData`.
+	#[inline]
+	pub unsafe fn type_of(&self, index: i32) -> LuaType {
+		LuaType::try_from((LUA_SHARED.lua_type)(*self, index)).unwrap_or(LuaType::UserData)
+	}
+
+	#[inline(always)]
+	/// Returns whether the value at the given index is of the given type.
+	pub unsafe fn is(&self, index: i32, lua_type: LuaType) -> bool {
+		(LUA_SHARED.lua_type)(*self, index) == lua_type as i32
 	}
 
 	#[inline(always)]
@@ -111,6 +123,18 @@ impl LuaState {
 		(LUA_SHARED.lua_gettop)(*self)
 	}
 
+	#[inline(always)]
+	/// Converts a relative stack index (such as `-1`) into an absolute one that stays valid as values are pushed.
+	///
+	/// Pseudo-indices (registry, globals, upvalues) are returned unchanged.
+	pub unsafe fn abs_index(&self, index: i32) -> i32 {
+		if index < 0 && index > LUA_REGISTRYINDEX {
+			self.get_top() + index + 1
+		} else {
+			index
+		}
+	}
+
 	#[inline(always)]
 	/// Pops the stack, inserts the value into the registry table, and returns the registry index of the value.
 	///
@@ -134,12 +158,12 @@ impl LuaState {
 	#[inline(always)]
 	/// You may be looking for `is_none_or_nil`
 	pub unsafe fn is_nil(&self, index: i32) -> bool {
-		(LUA_SHARED.lua_type)(*self, index) == LUA_TNIL
+		self.is(index, LuaType::Nil)
 	}
 
 	#[inline(always)]
 	pub unsafe fn is_none(&self, index: i32) -> bool {
-		(LUA_SHARED.lua_type)(*self, index) == LUA_TNONE
+		self.is(index, LuaType::None)
 	}
 
 	#[inline(always)]
@@ -149,17 +173,51 @@ impl LuaState {
 
 	#[inline(always)]
 	pub unsafe fn is_function(&self, index: i32) -> bool {
-		(LUA_SHARED.lua_type)(*self, index) == LUA_TFUNCTION
+		self.is(index, LuaType::Function)
+	}
+
+	#[inline(always)]
+	/// Returns whether the value is a C function, rather than a Lua function.
+	pub unsafe fn is_cfunction(&self, index: i32) -> bool {
+		(LUA_SHARED.lua_iscfunction)(*self, index) == 1
 	}
 
 	#[inline(always)]
 	pub unsafe fn is_table(&self, index: i32) -> bool {
-		(LUA_SHARED.lua_type)(*self, index) == LUA_TTABLE
+		self.is(index, LuaType::Table)
 	}
 
 	#[inline(always)]
 	pub unsafe fn is_boolean(&self, index: i32) -> bool {
-		(LUA_SHARED.lua_type)(*self, index) == LUA_TBOOLEAN
+		self.is(index, LuaType::Boolean)
+	}
+
+	#[inline(always)]
+	/// Returns whether the value is a number. Unlike `lua_isnumber`, numeric strings are not numbers.
+	pub unsafe fn is_number(&self, index: i32) -> bool {
+		self.is(index, LuaType::Number)
+	}
+
+	#[inline(always)]
+	/// Returns whether the value is a string. Unlike `lua_isstring`, numbers are not strings.
+	pub unsafe fn is_string(&self, index: i32) -> bool {
+		self.is(index, LuaType::String)
+	}
+
+	#[inline(always)]
+	/// Returns whether the value is a full userdata. See `is_light_userdata`.
+	pub unsafe fn is_userdata(&self, index: i32) -> bool {
+		self.is(index, LuaType::UserData)
+	}
+
+	#[inline(always)]
+	pub unsafe fn is_light_userdata(&self, index: i32) -> bool {
+		self.is(index, LuaType::LightUserData)
+	}
+
+	#[inline(always)]
+	pub unsafe fn is_thread(&self, index: i32) -> bool {
+		self.is(index, LuaType::Thread)
 	}
 
 	#[inline(always)]
@@ -283,6 +341,10 @@ impl LuaState {
 	}
 
 	pub unsafe fn lua_type_name(&self, lua_type_id: i32) -> Cow<'_, str> {
+		if let Ok(lua_type) = LuaType::try_from(lua_type_id) {
+			return Cow::Borrowed(lua_type.name());
+		}
+
 		let hackfix = self.get_top(); // https://github.com/Facepunch/garrysmod-issues/issues/5134
 
 		let type_str_ptr = (LUA_SHARED.lua_typename)(*self, lua_type_id);
@@ -326,6 +388,8 @@ impl LuaState {
 	#[inline(always)]
 	/// Creates a closure, which can be used as a function with stored data (upvalues)
 	///
+	/// To capture Rust values instead, use `push_rust_closure`.
+	///
 	/// ## Example
 	///
 	/// ```ignore
@@ -364,6 +428,14 @@ impl LuaState {
 		self.push_value(self.upvalue_index(n));
 	}
 
+	#[inline(always)]
+	/// Pops a value from the stack and sets it as upvalue `n` of the function at the given index.
+	///
+	/// Returns false, leaving the value on the stack, if the function doesn't have that many upvalues.
+	pub unsafe fn set_upvalue(&self, funcindex: i32, n: i32) -> bool {
+		!(LUA_SHARED.lua_setupvalue)(*self, funcindex, n).is_null()
+	}
+
 	#[inline(always)]
 	/// Equivalent to C `lua_upvalueindex` macro
 	pub const fn upvalue_index(&self, idx: i32) -> i32 {
@@ -465,12 +537,12 @@ impl LuaState {
 
 	#[inline(always)]
 	pub unsafe fn check_table(&self, arg: i32) {
-		(LUA_SHARED.lual_checktype)(*self, arg, LUA_TTABLE)
+		(LUA_SHARED.lual_checktype)(*self, arg, LuaType::Table as i32)
 	}
 
 	#[inline(always)]
 	pub unsafe fn check_function(&self, arg: i32) {
-		(LUA_SHARED.lual_checktype)(*self, arg, LUA_TFUNCTION)
+		(LUA_SHARED.lual_checktype)(*self, arg, LuaType::Function as i32)
 	}
 
 	#[inline(always)]
@@ -485,7 +557,7 @@ impl LuaState {
 
 	#[inline(always)]
 	pub unsafe fn check_boolean(&self, arg: i32) -> bool {
-		(LUA_SHARED.lual_checktype)(*self, arg, LUA_TBOOLEAN);
+		(LUA_SHARED.lual_checktype)(*self, arg, LuaType::Boolean as i32);
 		(LUA_SHARED.lua_toboolean)(*self, arg) == 1
 	}
 
@@ -525,6 +597,32 @@ impl LuaState {
 		(LUA_SHARED.lua_rawseti)(*self, t, index)
 	}
 
+	#[inline(always)]
+	/// Like `get_table`, but without invoking metamethods.
+	pub unsafe fn raw_get(&self, index: i32) {
+		(LUA_SHARED.lua_rawget)(*self, index)
+	}
+
+	#[inline(always)]
+	/// Like `set_table`, but without invoking metamethods.
+	pub unsafe fn raw_set(&self, index: i32) {
+		(LUA_SHARED.lua_rawset)(*self, index)
+	}
+
+	#[inline(always)]
+	/// Pushes the environment table of the function, thread or userdata at the given index.
+	pub unsafe fn get_fenv(&self, index: i32) {
+		(LUA_SHARED.lua_getfenv)(*self, index)
+	}
+
+	#[inline(always)]
+	/// Pops a table from the stack and sets it as the environment of the function, thread or userdata at the given index.
+	///
+	/// Returns false if the value at the given index can't have an environment.
+	pub unsafe fn set_fenv(&self, index: i32) -> bool {
+		(LUA_SHARED.lua_setfenv)(*self, index) != 0
+	}
+
 	#[inline(always)]
 	pub unsafe fn next(&self, index: i32) -> i32 {
 		(LUA_SHARED.lua_next)(*self, index)
@@ -570,6 +668,14 @@ impl LuaState {
 		(LUA_SHARED.lua_equal)(*self, index1, index2) == 1
 	}
 
+	#[inline(always)]
+	/// Ensures that there are at least `extra` free stack slots, growing the stack if needed.
+	///
+	/// Returns false if the stack could not grow to that size.
+	pub unsafe fn check_stack(&self, extra: i32) -> bool {
+		(LUA_SHARED.lua_checkstack)(*self, extra) != 0
+	}
+
 	#[inline(always)]
 	/// See `call`
 	pub unsafe fn coroutine_resume_call(&self, narg: i32) {
@@ -611,38 +717,51 @@ impl LuaState {
 		(LUA_SHARED.lual_newmetatable)(*self, name) == 0
 	}
 
-	pub unsafe fn new_userdata<T: Sized>(&self, data: T, metatable: Option<i32>) -> *mut T {
-		let has_metatable = if std::mem::needs_drop::<T>() {
-			if let Some(metatable) = metatable {
+	/// Moves `data` into a new userdata and pushes it onto the stack, optionally setting the metatable at the given index as its metatable.
+	///
+	/// If `metatable` is `None`, the userdata gets a metatable shared by every `T` (see `userdata_metatable`). Otherwise, if `T` needs dropping, a `__gc` metamethod is set on the given metatable. Only that field is stubbed out when the module is unloaded (see `neutralize_field_on_unload`): the rest of the metatable is the caller's, so register it with `neutralize_on_unload` if it holds this module's functions.
+	///
+	/// The value is stored after a small header which identifies its type. Use `try_rust_userdata`/`check_rust_userdata` to get it back from the stack, rather than casting the result of `to_userdata`.
+	///
+	/// Lua only guarantees the alignment its own types need (8 bytes), so this fails to compile if `T` is over-aligned. Use `new_boxed_userdata` for such types.
+	pub unsafe fn new_userdata<T: 'static>(&self, data: T, metatable: Option<i32>) -> *mut T {
+		let ptr = match metatable {
+			Some(metatable) => {
+				let metatable = self.abs_index(metatable);
+				if std::mem::needs_drop::<T>() {
+					self.push_function(crate::userdata::__gc::<T>);
+					self.set_field(metatable, crate::lua_string!("__gc"));
+					self.neutralize_field_on_unload(metatable, "__gc");
+				}
+
+				let ptr = crate::userdata::alloc_rust_userdata(*self, data);
 				self.push_value(metatable);
-			} else {
-				self.new_table();
+				ptr
+			},
+			None => {
+				let ptr = crate::userdata::alloc_rust_userdata(*self, data);
+				self.userdata_metatable::<T>();
+				ptr
 			}
-			self.push_function(crate::userdata::__gc::<T>);
-			self.set_field(-2, crate::lua_string!("__gc"));
-			true
-		} else if let Some(metatable) = metatable {
-			self.push_value(metatable);
-			true
-		} else {
-			false
 		};
-
-		let ptr = (LUA_SHARED.lua_newuserdata)(*self, std::mem::size_of::<T>()) as *mut T;
-
-		debug_assert_eq!(ptr as usize % std::mem::align_of::<T>(), 0, "Lua userdata is unaligned!");
-
-		if has_metatable {
-			self.push_value(-2);
-			self.set_metatable(-2);
-			self.remove(self.get_top() - 1);
-			self.remove(self.get_top() - 1);
-		}
-
-		ptr.write(data);
+		self.set_metatable(-2);
 		ptr
 	}
 
+	/// Like `new_userdata`, but the userdata holds a box rather than the value itself, and its `__gc` metamethod drops the box.
+	///
+	/// This allows unsized values, such as trait objects, and types aligned beyond what Lua guarantees for userdata. Use `try_boxed_userdata`/`check_boxed_userdata` with the same `T` to get it back from the stack:
+	///
+	/// ```ignore
+	/// let plugin: Box<dyn Plugin> = Box::new(MyPlugin::default());
+	/// lua.new_boxed_userdata(plugin, None);
+	///
+	/// lua.check_boxed_userdata::<dyn Plugin>(-1).think();
+	/// ```
+	pub unsafe fn new_boxed_userdata<T: ?Sized + 'static>(&self, data: Box<T>, metatable: Option<i32>) -> *mut T {
+		&mut **self.new_userdata(data, metatable)
+	}
+
 	#[cold]
 	pub unsafe fn error<S: AsRef<str>>(&self, msg: S) -> ! {
 		self.push_string(msg.as_ref());
@@ -650,6 +769,15 @@ impl LuaState {
 		unreachable!()
 	}
 
+	/// Raises a "bad argument" error for argument `arg` of the running C function, in the same format as Lua's standard library.
+	#[cold]
+	pub unsafe fn arg_error<S: AsRef<str>>(&self, arg: i32, msg: S) -> ! {
+		self.push_string(msg.as_ref());
+		let msg = (LUA_SHARED.lua_tolstring)(*self, -1, std::ptr::null_mut());
+		(LUA_SHARED.lual_argerror)(*self, arg, msg);
+		unreachable!()
+	}
+
 	pub unsafe fn debug_getinfo_from_ar(&self, ar: &mut LuaDebug, what: LuaString) -> Result<(), ()> {
 		if (LUA_SHARED.lua_getinfo)(*self, what, ar as *mut LuaDebug) != 0 {
 			Ok(())
@@ -684,63 +812,6 @@ impl LuaState {
 		}
 		None
 	}
-
-	pub unsafe fn dump_stack(&self) {
-		let top = self.get_top();
-		println!("\n=== STACK DUMP ===");
-		println!("Stack size: {}", top);
-		for i in 1..=top {
-			let lua_type = self.lua_type(i);
-			let lua_type_name = self.lua_type_name(lua_type);
-			match lua_type_name.as_ref() {
-				"string" => println!("{}. {}: {:?}", i, lua_type_name, {
-					self.push_value(i);
-					let str = self.get_string(-1);
-					self.pop();
-					str
-				}),
-				"boolean" => println!("{}. {}: {:?}", i, lua_type_name, {
-					self.push_value(i);
-					let bool = self.get_boolean(-1);
-					self.pop();
-					bool
-				}),
-				"number" => println!("{}. {}: {:?}", i, lua_type_name, {
-					self.push_value(i);
-					let n = self.to_number(-1);
-					self.pop();
-					n
-				}),
-				_ => println!("{}. {}", i, lua_type_name),
-			}
-		}
-		println!();
-	}
-
-	pub unsafe fn dump_val(&self, index: i32) -> String {
-		let lua_type_name = self.lua_type_name(self.lua_type(index));
-		match lua_type_name.as_ref() {
-			"string" => {
-				self.push_value(index);
-				let str = self.get_string(-1);
-				self.pop();
-				format!("{:?}", str.unwrap().into_owned())
-			},
-			"boolean" => {
-				self.push_value(index);
-				let boolean = self.get_boolean(-1);
-				self.pop();
-				format!("{}", boolean)
-			},
-			"number" => {
-				self.push_value(index);
-				let n = self.to_number(-1);
-				self.pop();
-				format!("{}", n)
-			},
-			_ => lua_type_name.into_owned(),
-		}
-	}
 }
 impl std::ops::Deref for LuaState {
 	type Target = *mut std::ffi::c_void;
@@ -0,0 +1,66 @@
+use crate::lua::*;
+
+/// The type of a value on the Lua stack, as returned by `State::type_of`.
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum LuaType {
+	/// An invalid (unused) stack index
+	None = LUA_TNONE,
+
+	Nil = LUA_TNIL,
+	Boolean = LUA_TBOOLEAN,
+	LightUserData = LUA_TLIGHTUSERDATA,
+	Number = LUA_TNUMBER,
+	String = LUA_TSTRING,
+	Table = LUA_TTABLE,
+	Function = LUA_TFUNCTION,
+	UserData = LUA_TUSERDATA,
+	Thread = LUA_TTHREAD,
+}
+impl LuaType {
+	/// Returns the name of this type, as `type()` would.
+	pub const fn name(self) -> &'static str {
+		match self {
+			LuaType::None => "no value",
+			LuaType::Nil => "nil",
+			LuaType::Boolean => "boolean",
+			LuaType::LightUserData | LuaType::UserData => "userdata",
+			LuaType::Number => "number",
+			LuaType::String => "string",
+			LuaType::Table => "table",
+			LuaType::Function => "function",
+			LuaType::Thread => "thread",
+		}
+	}
+}
+impl TryFrom<i32> for LuaType {
+	type Error = i32;
+
+	fn try_from(lua_type_id: i32) -> Result<Self, Self::Error> {
+		Ok(match lua_type_id {
+			LUA_TNONE => LuaType::None,
+			LUA_TNIL => LuaType::Nil,
+			LUA_TBOOLEAN => LuaType::Boolean,
+			LUA_TLIGHTUSERDATA => LuaType::LightUserData,
+			LUA_TNUMBER => LuaType::Number,
+			LUA_TSTRING => LuaType::String,
+			LUA_TTABLE => LuaType::Table,
+			LUA_TFUNCTION => LuaType::Function,
+			LUA_TUSERDATA => LuaType::UserData,
+			LUA_TTHREAD => LuaType::Thread,
+			_ => return Err(lua_type_id),
+		})
+	}
+}
+impl From<LuaType> for i32 {
+	#[inline(always)]
+	fn from(lua_type: LuaType) -> Self {
+		lua_type as i32
+	}
+}
+impl std::fmt::Display for LuaType {
+	#[inline]
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(self.name())
+	}
+}
@@ -8,14 +8,51 @@ pub use import::*;
 mod lua_state;
 pub use lua_state::LuaState as State;
 
+mod lua_type;
+pub use lua_type::LuaType;
+
 mod push;
 pub use push::*;
 
+mod read;
+pub use read::*;
+
 mod returns;
 pub use returns::ValuesReturned;
+#[doc(hidden)]
+pub use returns::__LuaReturn;
+
+mod reference;
+pub use reference::{LuaRef, LuaWeakRef};
+
+mod value;
+pub use value::Value;
+pub(crate) use value::read_tagged_userdata;
+
+mod dump;
+pub use dump::DUMP_DEFAULT_DEPTH;
+
+mod backtrace;
+pub use backtrace::Frame;
+
+mod multi;
+pub use multi::{FromLuaMulti, PushToLuaMulti, Variadic};
+#[doc(hidden)]
+pub use multi::__check_arg;
 
 mod raw_bind;
 
+mod closure;
+
+mod export;
+#[doc(hidden)]
+pub use export::{LuaExport, __LUA_EXPORTS, __register_exports};
+
+mod unload;
+pub(crate) use unload::{forget_metatable_on_unload, wrap_function};
+#[doc(hidden)]
+pub use unload::__unload;
+
 #[derive(Debug, Clone)]
 pub enum LuaError {
 	/// Out of memory
@@ -63,7 +100,7 @@ macro_rules! lua_string {
 ///
 /// # Example
 ///
-/// ```rust,norun
+/// ```ignore
 /// lua_stack_guard!(lua => {
 ///     lua.get_global(lua_string!("hook"));
 ///     lua.get_field(-1, lua_string!("Add"));
@@ -126,15 +163,15 @@ pub struct LuaDebug {
 #[inline(always)]
 /// Loads lua_shared and imports all functions. This is already done for you if you add `#[gmod::gmod13_open]` to your `gmod13_open` function.
 pub unsafe fn load() {
-	import::LUA_SHARED.load()
+	(&*std::ptr::addr_of!(import::LUA_SHARED)).load()
 }
 
 thread_local! {
 	#[cfg(debug_assertions)]
-	static LUA: Cell<Option<State>> = Cell::new(None);
+	static LUA: Cell<Option<State>> = const { Cell::new(None) };
 
 	#[cfg(not(debug_assertions))]
-	static LUA: Cell<State> = Cell::new(State(std::ptr::null_mut()));
+	static LUA: Cell<State> = const { Cell::new(State(std::ptr::null_mut())) };
 }
 /// Acquires a pointer to the Lua state for the current thread.
 ///
@@ -0,0 +1,196 @@
+use crate::lua::*;
+
+/// Any number of values that can be pushed to the Lua stack, such as the arguments of a function call.
+///
+/// Implemented for `()`, any single `PushToLua` value, and tuples of them.
+pub trait PushToLuaMulti: Sized {
+	/// Pushes these values to the Lua stack, returning how many were pushed.
+	unsafe fn push_to_lua_multi(self, lua: State) -> i32;
+}
+
+/// Values that can be read from consecutive slots of the Lua stack, such as the results of a function call.
+///
+/// Implemented for `()`, any single `FromLua` value, `Variadic`, and tuples of them. Only the last element of a tuple may be `Variadic`.
+pub trait FromLuaMulti: Sized {
+	/// How many stack slots this reads, or `LUA_MULTRET` if it reads every value up to the top of the stack.
+	const COUNT: i32;
+
+	/// Reads `COUNT` values starting at the given index, leaving the stack unchanged.
+	unsafe fn from_lua_multi(lua: State, index: i32) -> Result<Self, FromLuaError>;
+}
+
+impl<T: PushToLua> PushToLuaMulti for T {
+	#[inline]
+	unsafe fn push_to_lua_multi(self, lua: State) -> i32 {
+		self.push_to_lua(lua);
+		1
+	}
+}
+
+impl<T: FromLua> FromLuaMulti for T {
+	const COUNT: i32 = 1;
+
+	#[inline]
+	unsafe fn from_lua_multi(lua: State, index: i32) -> Result<Self, FromLuaError> {
+		T::from_lua(lua, index)
+	}
+}
+
+impl PushToLuaMulti for () {
+	#[inline]
+	unsafe fn push_to_lua_multi(self, _lua: State) -> i32 {
+		0
+	}
+}
+
+impl FromLuaMulti for () {
+	const COUNT: i32 = 0;
+
+	#[inline]
+	unsafe fn from_lua_multi(_lua: State, _index: i32) -> Result<Self, FromLuaError> {
+		Ok(())
+	}
+}
+
+#[doc(hidden)]
+/// Reads argument `arg` of a typed `#[lua_function]`, raising a "bad argument" Lua error if it can't be converted, and moves `arg` on to the next argument.
+pub unsafe fn __check_arg<T: FromLuaMulti>(lua: State, arg: &mut i32) -> T {
+	match T::from_lua_multi(lua, *arg) {
+		Ok(val) => {
+			if T::COUNT != LUA_MULTRET {
+				*arg += T::COUNT;
+			}
+			val
+		},
+		Err(err) => lua.arg_error(err.index, err.to_string()),
+	}
+}
+
+/// The `COUNT` of a tuple, given the `COUNT` of each of its elements.
+///
+/// Fails to compile if any element but the last takes every remaining value, as the elements after it would never get any.
+const fn total_count(counts: &[i32]) -> i32 {
+	let mut total = 0;
+	let mut i = 0;
+	while i < counts.len() {
+		if counts[i] == LUA_MULTRET {
+			assert!(i == counts.len() - 1, "only the last element of a tuple can be variadic");
+			return LUA_MULTRET;
+		}
+		total += counts[i];
+		i += 1;
+	}
+	total
+}
+
+macro_rules! impl_multi_tuples {
+	($(($($ty:ident),+)),*) => {$(
+		impl<$($ty: PushToLua),+> PushToLuaMulti for ($($ty,)+) {
+			#[inline]
+			#[allow(non_snake_case)]
+			unsafe fn push_to_lua_multi(self, lua: State) -> i32 {
+				let ($($ty,)+) = self;
+				let mut n = 0;
+				$($ty.push_to_lua(lua); n += 1;)+
+				n
+			}
+		}
+
+		impl<$($ty: FromLuaMulti),+> FromLuaMulti for ($($ty,)+) {
+			const COUNT: i32 = total_count(&[$($ty::COUNT),+]);
+
+			#[inline]
+			unsafe fn from_lua_multi(lua: State, index: i32) -> Result<Self, FromLuaError> {
+				// Checks that only the last element is variadic
+				let _ = Self::COUNT;
+
+				let mut index = lua.abs_index(index);
+				Ok(($({
+					let value = $ty::from_lua_multi(lua, index)?;
+					index += $ty::COUNT;
+					value
+				},)+))
+			}
+		}
+	)*};
+}
+impl_multi_tuples! {
+	(A),
+	(A, B),
+	(A, B, C),
+	(A, B, C, D),
+	(A, B, C, D, E),
+	(A, B, C, D, E, F),
+	(A, B, C, D, E, F, G),
+	(A, B, C, D, E, F, G, H)
+}
+
+/// All the remaining values on the stack, such as the trailing arguments of a vararg function.
+///
+/// ```ignore
+/// #[lua_function]
+/// fn sum(numbers: Variadic<f64>) -> f64 {
+///     numbers.iter().sum()
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Variadic<T>(pub Vec<T>);
+impl<T> Variadic<T> {
+	#[inline]
+	pub fn into_inner(self) -> Vec<T> {
+		self.0
+	}
+}
+impl<T> std::ops::Deref for Variadic<T> {
+	type Target = Vec<T>;
+
+	#[inline]
+	fn deref(&self) -> &Vec<T> {
+		&self.0
+	}
+}
+impl<T> std::ops::DerefMut for Variadic<T> {
+	#[inline]
+	fn deref_mut(&mut self) -> &mut Vec<T> {
+		&mut self.0
+	}
+}
+impl<T> From<Vec<T>> for Variadic<T> {
+	#[inline]
+	fn from(values: Vec<T>) -> Self {
+		Variadic(values)
+	}
+}
+impl<T> FromIterator<T> for Variadic<T> {
+	#[inline]
+	fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+		Variadic(iter.into_iter().collect())
+	}
+}
+impl<T> IntoIterator for Variadic<T> {
+	type Item = T;
+	type IntoIter = std::vec::IntoIter<T>;
+
+	#[inline]
+	fn into_iter(self) -> Self::IntoIter {
+		self.0.into_iter()
+	}
+}
+impl<T: FromLua> FromLuaMulti for Variadic<T> {
+	const COUNT: i32 = LUA_MULTRET;
+
+	unsafe fn from_lua_multi(lua: State, index: i32) -> Result<Self, FromLuaError> {
+		let index = lua.abs_index(index);
+		(index..=lua.get_top()).map(|index| T::from_lua(lua, index)).collect()
+	}
+}
+impl<T: PushToLua> PushToLuaMulti for Variadic<T> {
+	#[inline]
+	unsafe fn push_to_lua_multi(self, lua: State) -> i32 {
+		let n = self.0.len() as i32;
+		for value in self.0 {
+			value.push_to_lua(lua);
+		}
+		n
+	}
+}
@@ -40,6 +40,6 @@ impl State {
 	///
 	/// Note, this may be a somewhat expensive operation, so storing its result in some way is recommended.
 	pub unsafe fn raw_bind<F: CLuaFunction>(&self, symbol: &[u8]) -> Result<F, libloading::Error> {
-		LUA_SHARED.library.get::<F>(symbol).map(|f| *f)
+		(&*std::ptr::addr_of!(LUA_SHARED)).library.get::<F>(symbol).map(|f| *f)
 	}
 }
\ No newline at end of file
@@ -0,0 +1,172 @@
+use std::borrow::Cow;
+
+use crate::lua::*;
+
+/// The value on the stack could not be converted to the requested Rust type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FromLuaError {
+	/// What we were expecting to find
+	pub expected: Cow<'static, str>,
+
+	/// What we actually found
+	pub got: Cow<'static, str>,
+
+	/// The absolute stack index of the value, which for a tuple is the element that couldn't be converted
+	pub index: i32,
+}
+impl FromLuaError {
+	/// Creates an error describing that the value at `index` was not the expected type.
+	pub unsafe fn type_mismatch<S: Into<Cow<'static, str>>>(lua: State, index: i32, expected: S) -> Self {
+		FromLuaError {
+			expected: expected.into(),
+			got: Cow::Borrowed(lua.get_type(index)),
+			index: lua.abs_index(index),
+		}
+	}
+}
+impl std::fmt::Display for FromLuaError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{} expected, got {}", self.expected, self.got)
+	}
+}
+impl std::error::Error for FromLuaError {}
+
+pub trait FromLua: Sized {
+	#[doc(hidden)]
+	/// Set for types that refer to their stack slot rather than owning the value, like `AnyValue`, so can't be read from values that are popped straight after.
+	const BORROWS_STACK: bool = false;
+
+	/// Reads the value at the given index of the Lua stack, leaving the stack unchanged.
+	unsafe fn from_lua(lua: crate::lua::State, index: i32) -> Result<Self, FromLuaError>;
+}
+
+macro_rules! from_lua_numbers {
+	{$($ty:ty),*} => {$(
+		impl FromLua for $ty {
+			#[inline]
+			unsafe fn from_lua(lua: crate::lua::State, index: i32) -> Result<Self, FromLuaError> {
+				if lua.is_number(index) {
+					Ok(lua.to_number(index) as _)
+				} else {
+					Err(FromLuaError::type_mismatch(lua, index, "number"))
+				}
+			}
+		}
+	)*};
+}
+from_lua_numbers!(f64, f32, u8, i8, u16, i16, u32, i32, u64, i64, usize, isize);
+
+impl FromLua for bool {
+	#[inline]
+	unsafe fn from_lua(lua: crate::lua::State, index: i32) -> Result<Self, FromLuaError> {
+		if lua.is_boolean(index) {
+			Ok(lua.get_boolean(index))
+		} else {
+			Err(FromLuaError::type_mismatch(lua, index, "boolean"))
+		}
+	}
+}
+
+impl FromLua for Vec<u8> {
+	#[inline]
+	unsafe fn from_lua(lua: crate::lua::State, index: i32) -> Result<Self, FromLuaError> {
+		if lua.is_string(index) {
+			Ok(lua.get_binary_string(index).unwrap_or_default().to_vec())
+		} else {
+			Err(FromLuaError::type_mismatch(lua, index, "string"))
+		}
+	}
+}
+
+impl FromLua for String {
+	#[inline]
+	unsafe fn from_lua(lua: crate::lua::State, index: i32) -> Result<Self, FromLuaError> {
+		if lua.is_string(index) {
+			Ok(lua.get_string(index).unwrap_or_default().into_owned())
+		} else {
+			Err(FromLuaError::type_mismatch(lua, index, "string"))
+		}
+	}
+}
+
+impl FromLua for LuaRef {
+	#[inline]
+	unsafe fn from_lua(lua: crate::lua::State, index: i32) -> Result<Self, FromLuaError> {
+		Ok(LuaRef::from_stack(lua, index))
+	}
+}
+
+/// `nil`, or no value at all (such as a missing argument), is `None`.
+impl<T: FromLua> FromLua for Option<T> {
+	const BORROWS_STACK: bool = T::BORROWS_STACK;
+
+	#[inline]
+	unsafe fn from_lua(lua: crate::lua::State, index: i32) -> Result<Self, FromLuaError> {
+		if lua.is_none_or_nil(index) {
+			Ok(None)
+		} else {
+			match T::from_lua(lua, index) {
+				Ok(val) => Ok(Some(val)),
+				Err(err) => Err(FromLuaError {
+					expected: Cow::Owned(format!("{} or nil", err.expected)),
+					..err
+				}),
+			}
+		}
+	}
+}
+
+/// Any value, left where it is on the stack rather than converted.
+///
+/// This is the equivalent of `luaL_checkany`: it accepts anything but no value at all. Use `Option<AnyValue>` to also accept that. It only refers to a stack slot, so it's only valid until that slot is popped or overwritten.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnyValue {
+	/// The absolute stack index of the value
+	pub index: i32,
+	pub lua_type: LuaType,
+}
+impl AnyValue {
+	#[inline]
+	pub fn is_nil(&self) -> bool {
+		self.lua_type == LuaType::Nil
+	}
+
+	/// Pushes a copy of the value onto the stack.
+	#[inline]
+	pub unsafe fn push(&self, lua: crate::lua::State) {
+		lua.push_value(self.index);
+	}
+
+	/// Converts the value to `T`.
+	#[inline]
+	pub unsafe fn read<T: FromLua>(&self, lua: crate::lua::State) -> Result<T, FromLuaError> {
+		T::from_lua(lua, self.index)
+	}
+}
+impl FromLua for AnyValue {
+	const BORROWS_STACK: bool = true;
+
+	#[inline]
+	unsafe fn from_lua(lua: crate::lua::State, index: i32) -> Result<Self, FromLuaError> {
+		match lua.type_of(index) {
+			LuaType::None => Err(FromLuaError::type_mismatch(lua, index, "value")),
+			lua_type => Ok(AnyValue { index: lua.abs_index(index), lua_type }),
+		}
+	}
+}
+impl PushToLua for AnyValue {
+	#[inline]
+	unsafe fn push_to_lua(self, lua: crate::lua::State) {
+		self.push(lua);
+	}
+}
+
+impl State {
+	/// Reads argument `arg` as `T`, raising a "bad argument" Lua error if it can't be converted.
+	pub unsafe fn check<T: FromLua>(&self, arg: i32) -> T {
+		match T::from_lua(*self, arg) {
+			Ok(val) => val,
+			Err(err) => self.arg_error(arg, err.to_string()),
+		}
+	}
+}
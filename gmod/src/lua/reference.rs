@@ -0,0 +1,259 @@
+use std::{cell::RefCell, ffi::c_void};
+
+use crate::lua::{self, LuaReference, PushToLua, State, LUA_NOREF, LUA_REFNIL, LUA_REGISTRYINDEX};
+
+/// An owned, strong reference to a Lua value, stored in the registry.
+///
+/// The reference is freed from the registry when this is dropped. Dropping, cloning and comparing use the Lua state of the current thread (see `lua::state`), so a `LuaRef` must be dropped on the Lua thread, before the module is closed by `gmod13_close`. Use `std::mem::forget` on any that would outlive it.
+///
+/// Two `LuaRef`s are equal if their values are raw equal (see `lua_rawequal`): the same object, or equal numbers, booleans or strings.
+pub struct LuaRef {
+	reference: LuaReference,
+	ptr: *const c_void,
+}
+impl LuaRef {
+	/// Pops the value at the top of the stack and references it.
+	pub unsafe fn new(lua: State) -> LuaRef {
+		let ptr = lua.to_pointer(-1);
+		LuaRef {
+			reference: lua.reference(),
+			ptr,
+		}
+	}
+
+	/// References the value at the given index of the stack, leaving the stack unchanged.
+	pub unsafe fn from_stack(lua: State, index: i32) -> LuaRef {
+		lua.push_value(index);
+		LuaRef::new(lua)
+	}
+
+	#[inline(always)]
+	/// Pushes the referenced value onto the stack.
+	pub unsafe fn push(&self, lua: State) {
+		lua.from_reference(self.reference);
+	}
+
+	#[inline(always)]
+	/// Returns the raw registry reference index.
+	pub fn id(&self) -> LuaReference {
+		self.reference
+	}
+
+	#[inline(always)]
+	/// Returns the address of the referenced object, as returned by `lua_topointer`.
+	pub fn as_ptr(&self) -> *const c_void {
+		self.ptr
+	}
+
+	/// Creates a weak reference to the same value.
+	pub unsafe fn downgrade(&self, lua: State) -> LuaWeakRef {
+		self.push(lua);
+		LuaWeakRef::new(lua)
+	}
+}
+impl Drop for LuaRef {
+	fn drop(&mut self) {
+		unsafe { lua::state().dereference(self.reference) }
+	}
+}
+impl Clone for LuaRef {
+	fn clone(&self) -> Self {
+		unsafe {
+			let lua = lua::state();
+			self.push(lua);
+			LuaRef::new(lua)
+		}
+	}
+}
+impl PartialEq for LuaRef {
+	fn eq(&self, other: &Self) -> bool {
+		if self.reference == other.reference {
+			return true;
+		}
+		unsafe {
+			let lua = lua::state();
+			self.push(lua);
+			other.push(lua);
+			let eq = lua.raw_equal(-1, -2);
+			lua.pop_n(2);
+			eq
+		}
+	}
+}
+impl std::fmt::Debug for LuaRef {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "LuaRef({:p})", self.ptr)
+	}
+}
+impl PushToLua for &LuaRef {
+	#[inline]
+	unsafe fn push_to_lua(self, lua: State) {
+		self.push(lua);
+	}
+}
+impl PushToLua for LuaRef {
+	#[inline]
+	unsafe fn push_to_lua(self, lua: State) {
+		self.push(lua);
+	}
+}
+
+/// Marks the registry table holding weak references. Only its address is used.
+static WEAK_REFS_KEY: u8 = 0;
+
+/// Slots of the weak table handed out to `LuaWeakRef`s.
+///
+/// `luaL_ref` can't be used for the weak table: it looks for free slots with `lua_objlen`, which counts the holes left by collected values as free and would hand out a slot that a `LuaWeakRef` still owns.
+#[derive(Default)]
+struct WeakSlots {
+	/// The highest slot handed out so far
+	len: LuaReference,
+
+	/// Slots freed by dropped `LuaWeakRef`s
+	free: Vec<LuaReference>,
+}
+
+thread_local! {
+	static WEAK_SLOTS: RefCell<WeakSlots> = RefCell::new(WeakSlots::default());
+}
+
+/// Pushes the weak-valued table that `LuaWeakRef`s reference values in, creating it first if needed.
+unsafe fn push_weak_refs(lua: State) {
+	lua.push_lightuserdata(&WEAK_REFS_KEY as *const u8 as *mut c_void);
+	lua.raw_get(LUA_REGISTRYINDEX);
+	if lua.is_table(-1) {
+		return;
+	}
+	lua.pop();
+
+	lua.new_table();
+	lua.create_table(0, 1);
+	lua.push_string("v");
+	lua.set_field(-2, crate::lua_string!("__mode"));
+	lua.set_metatable(-2);
+
+	lua.push_lightuserdata(&WEAK_REFS_KEY as *const u8 as *mut c_void);
+	lua.push_value(-2);
+	lua.raw_set(LUA_REGISTRYINDEX);
+}
+
+/// An owned, weak reference to a Lua value.
+///
+/// Unlike `LuaRef`, this doesn't keep the value alive: once nothing else references it, it can be garbage collected and `upgrade` will fail. Values that aren't garbage collected, such as numbers and booleans, are always alive.
+///
+/// The reference is freed when this is dropped. Like `LuaRef`, this uses the Lua state of the current thread, so a `LuaWeakRef` must be dropped on the Lua thread before the module is closed.
+pub struct LuaWeakRef {
+	/// A slot of the weak table rather than the registry, `LUA_REFNIL` for nil, or `LUA_NOREF` for clones of collected references
+	reference: LuaReference,
+	ptr: *const c_void,
+}
+impl LuaWeakRef {
+	/// Pops the value at the top of the stack and references it weakly.
+	pub unsafe fn new(lua: State) -> LuaWeakRef {
+		let ptr = lua.to_pointer(-1);
+		if lua.is_nil(-1) {
+			lua.pop();
+			return LuaWeakRef { reference: LUA_REFNIL, ptr };
+		}
+
+		let reference = WEAK_SLOTS.with(|slots| {
+			let mut slots = slots.borrow_mut();
+			slots.free.pop().unwrap_or_else(|| {
+				slots.len += 1;
+				slots.len
+			})
+		});
+
+		push_weak_refs(lua);
+		lua.insert(-2);
+		lua.raw_seti(-2, reference);
+		lua.pop();
+		LuaWeakRef { reference, ptr }
+	}
+
+	/// Weakly references the value at the given index of the stack, leaving the stack unchanged.
+	pub unsafe fn from_stack(lua: State, index: i32) -> LuaWeakRef {
+		lua.push_value(index);
+		LuaWeakRef::new(lua)
+	}
+
+	/// Pushes the referenced value onto the stack and returns `true`, or pushes nothing and returns `false` if it has been garbage collected.
+	pub unsafe fn upgrade(&self, lua: State) -> bool {
+		match self.reference {
+			LUA_REFNIL => {
+				lua.push_nil();
+				return true;
+			},
+			LUA_NOREF => return false,
+			_ => {}
+		}
+
+		push_weak_refs(lua);
+		lua.raw_geti(-1, self.reference);
+		lua.remove(-2);
+		if lua.is_nil(-1) {
+			lua.pop();
+			false
+		} else {
+			true
+		}
+	}
+
+	/// Returns a strong reference to the value, or `None` if it has been garbage collected.
+	pub unsafe fn to_strong(&self, lua: State) -> Option<LuaRef> {
+		if self.upgrade(lua) {
+			Some(LuaRef::new(lua))
+		} else {
+			None
+		}
+	}
+
+	/// Returns whether the value hasn't been garbage collected yet.
+	pub unsafe fn is_alive(&self, lua: State) -> bool {
+		let alive = self.upgrade(lua);
+		if alive {
+			lua.pop();
+		}
+		alive
+	}
+
+	#[inline(always)]
+	/// Returns the address of the referenced object when the reference was created, as returned by `lua_topointer`.
+	///
+	/// This stays the same after the object is collected, and another object may be allocated at the same address.
+	pub fn as_ptr(&self) -> *const c_void {
+		self.ptr
+	}
+}
+impl Drop for LuaWeakRef {
+	fn drop(&mut self) {
+		if self.reference <= 0 {
+			return;
+		}
+		unsafe {
+			let lua = lua::state();
+			push_weak_refs(lua);
+			lua.push_nil();
+			lua.raw_seti(-2, self.reference);
+			lua.pop();
+		}
+		WEAK_SLOTS.with(|slots| slots.borrow_mut().free.push(self.reference));
+	}
+}
+impl Clone for LuaWeakRef {
+	fn clone(&self) -> Self {
+		unsafe {
+			let lua = lua::state();
+			if self.upgrade(lua) {
+				LuaWeakRef::new(lua)
+			} else {
+				LuaWeakRef { reference: LUA_NOREF, ptr: self.ptr }
+			}
+		}
+	}
+}
+impl std::fmt::Debug for LuaWeakRef {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "LuaWeakRef({:p})", self.ptr)
+	}
+}
@@ -70,4 +70,24 @@ impl<E: DisplayLuaError> From<Result<(), E>> for ValuesReturned {
 			Err(err) => unsafe { super::state().error(err.display_lua_error().as_ref()) }
 		}
 	}
-}
\ No newline at end of file
+}
+#[doc(hidden)]
+/// What a typed `#[lua_function]` can return: values to push, or a `Result` of them which raises its error.
+pub trait __LuaReturn {
+	unsafe fn __push_return(self, lua: super::State) -> i32;
+}
+impl<T: super::PushToLuaMulti> __LuaReturn for T {
+	#[inline(always)]
+	unsafe fn __push_return(self, lua: super::State) -> i32 {
+		self.push_to_lua_multi(lua)
+	}
+}
+impl<T: super::PushToLuaMulti, E: DisplayLuaError> __LuaReturn for Result<T, E> {
+	#[inline(always)]
+	unsafe fn __push_return(self, lua: super::State) -> i32 {
+		match self {
+			Ok(vals) => vals.push_to_lua_multi(lua),
+			Err(err) => lua.error(err.display_lua_error().as_ref()),
+		}
+	}
+}
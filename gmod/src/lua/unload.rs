@@ -0,0 +1,253 @@
+use std::ffi::{c_void, CStr};
+
+use crate::lua::{State, LUA_REGISTRYINDEX};
+
+/// Marks the registry table of tables holding this module's C functions. Only its address is used.
+static TABLES_KEY: u8 = 0;
+
+/// Marks the registry table of single fields holding this module's C functions, which maps each table to a table of its fields and the functions they were set to. Only its address is used.
+static FIELDS_KEY: u8 = 0;
+
+/// Marks the registry table of Lua functions wrapping this module's closures. Only its address is used.
+static WRAPPERS_KEY: u8 = 0;
+
+/// Marks the registry set of names under which this module has stored metatables in the registry. Only its address is used.
+static METATABLES_KEY: u8 = 0;
+
+/// Marks the registry slot caching the function which creates wrappers. Only its address is used.
+static WRAP_KEY: u8 = 0;
+
+#[inline]
+fn key(key: &'static u8) -> *mut c_void {
+	key as *const u8 as *mut c_void
+}
+
+/// Pushes the weak-keyed registry set marked by `marker`, creating it first if needed.
+unsafe fn push_set(lua: State, marker: &'static u8) {
+	lua.push_lightuserdata(key(marker));
+	lua.raw_get(LUA_REGISTRYINDEX);
+	if lua.is_table(-1) {
+		return;
+	}
+	lua.pop();
+
+	lua.new_table();
+	lua.create_table(0, 1);
+	lua.push_string("k");
+	lua.set_field(-2, crate::lua_string!("__mode"));
+	lua.set_metatable(-2);
+
+	lua.push_lightuserdata(key(marker));
+	lua.push_value(-2);
+	lua.raw_set(LUA_REGISTRYINDEX);
+}
+
+/// Removes the registry set marked by `marker` and pushes it, or pushes nothing and returns `false` if it was never created.
+unsafe fn take_set(lua: State, marker: &'static u8) -> bool {
+	lua.push_lightuserdata(key(marker));
+	lua.raw_get(LUA_REGISTRYINDEX);
+	if !lua.is_table(-1) {
+		lua.pop();
+		return false;
+	}
+
+	lua.push_lightuserdata(key(marker));
+	lua.push_nil();
+	lua.raw_set(LUA_REGISTRYINDEX);
+	true
+}
+
+unsafe fn add_to_set(lua: State, marker: &'static u8, index: i32) {
+	let index = lua.abs_index(index);
+	push_set(lua, marker);
+	lua.push_value(index);
+	lua.push_boolean(true);
+	lua.raw_set(-3);
+	lua.pop();
+}
+
+/// Replaces the function at the top of the stack with a Lua function which calls it, and which is cut off from it when the module is unloaded.
+///
+/// This is for functions that Lua can keep anywhere, such as closures, so can't be found and replaced when the module is unloaded.
+///
+/// The wrapped function isn't tail called, so that it still has a name in error messages ("bad argument #1 to 'rust_closure'") and tracebacks.
+pub(crate) unsafe fn wrap_function(lua: State) {
+	lua.push_lightuserdata(key(&WRAP_KEY));
+	lua.raw_get(LUA_REGISTRYINDEX);
+	if !lua.is_function(-1) {
+		lua.pop();
+		lua.load_string(crate::lua_string!("local rust_closure = ... local function pass(...) return ... end return function(...) return pass(rust_closure(...)) end")).unwrap();
+		lua.push_lightuserdata(key(&WRAP_KEY));
+		lua.push_value(-2);
+		lua.raw_set(LUA_REGISTRYINDEX);
+	}
+	lua.insert(-2);
+	lua.call(1, 1);
+	add_to_set(lua, &WRAPPERS_KEY, -1);
+}
+
+/// Records that this module created the registry metatable `name`, so that it's removed from the registry when the module is unloaded.
+///
+/// Otherwise, a reloaded module would find its old, neutralized metatables under the same names and use them for new userdata.
+pub(crate) unsafe fn forget_metatable_on_unload(lua: State, name: &CStr) {
+	lua.push_string(&name.to_string_lossy());
+	add_to_set(lua, &METATABLES_KEY, -1);
+	lua.pop();
+}
+
+/// Removes the metatables recorded by `forget_metatable_on_unload` from the registry. Userdata still using them keep them.
+unsafe fn forget_metatables(lua: State) {
+	if !take_set(lua, &METATABLES_KEY) {
+		return;
+	}
+	let names = lua.abs_index(-1);
+
+	lua.push_nil();
+	while lua.next(names) != 0 {
+		lua.pop();
+		lua.push_value(-1);
+		lua.push_nil();
+		lua.raw_set(LUA_REGISTRYINDEX);
+	}
+
+	lua.pop();
+}
+
+/// Replaces every C function in the tables registered with `neutralize_on_unload` with a Lua stub.
+unsafe fn neutralize_tables(lua: State, noop: i32, unloaded: i32) {
+	if !take_set(lua, &TABLES_KEY) {
+		return;
+	}
+	let tables = lua.abs_index(-1);
+
+	lua.push_nil();
+	while lua.next(tables) != 0 {
+		lua.pop();
+		let table = lua.abs_index(-1);
+
+		lua.push_nil();
+		while lua.next(table) != 0 {
+			if lua.is_cfunction(-1) {
+				let gc = lua.is_string(-2) && lua.get_string(-2).as_deref() == Some("__gc");
+				lua.push_value(-2);
+				lua.push_value(if gc { noop } else { unloaded });
+				lua.raw_set(table);
+			}
+			lua.pop();
+		}
+	}
+
+	lua.pop();
+}
+
+/// Replaces the functions set in the fields registered with `neutralize_field_on_unload` with a Lua stub, unless they've been overwritten since.
+unsafe fn neutralize_fields(lua: State, noop: i32, unloaded: i32) {
+	if !take_set(lua, &FIELDS_KEY) {
+		return;
+	}
+	let tables = lua.abs_index(-1);
+
+	lua.push_nil();
+	while lua.next(tables) != 0 {
+		let fields = lua.abs_index(-1);
+		let table = lua.abs_index(-2);
+
+		lua.push_nil();
+		while lua.next(fields) != 0 {
+			lua.push_value(-2);
+			lua.raw_get(table);
+			if lua.raw_equal(-1, -2) {
+				let gc = lua.is_string(-3) && lua.get_string(-3).as_deref() == Some("__gc");
+				lua.push_value(-3);
+				lua.push_value(if gc { noop } else { unloaded });
+				lua.raw_set(table);
+			}
+			lua.pop_n(2);
+		}
+
+		lua.pop();
+	}
+
+	lua.pop();
+}
+
+/// Points every wrapper made by `wrap_function` at a Lua stub instead of the function it wraps.
+unsafe fn disconnect_wrappers(lua: State, unloaded: i32) {
+	lua.push_lightuserdata(key(&WRAP_KEY));
+	lua.push_nil();
+	lua.raw_set(LUA_REGISTRYINDEX);
+
+	if !take_set(lua, &WRAPPERS_KEY) {
+		return;
+	}
+	let wrappers = lua.abs_index(-1);
+
+	lua.push_nil();
+	while lua.next(wrappers) != 0 {
+		lua.pop();
+		lua.push_value(unloaded);
+		if !lua.set_upvalue(-2, 1) {
+			lua.pop();
+		}
+	}
+
+	lua.pop();
+}
+
+impl State {
+	/// Registers the table at the given index as holding this module's C functions, leaving the stack unchanged.
+	///
+	/// When the module is unloaded by `#[gmod13_close]`, every C function in the table is replaced with a Lua function that raises an error (or does nothing, for `__gc`), so that Lua can't call into the module after it's been unmapped. The metatables and module tables created by gmod-rs are already registered.
+	///
+	/// Only register tables that this module owns, as C functions from the engine or other modules are replaced too. Use `neutralize_field_on_unload` for a single field of a shared table such as `_G`. Closures pushed with `push_rust_closure` don't need registering.
+	///
+	/// The table isn't kept alive by this.
+	pub unsafe fn neutralize_on_unload(&self, index: i32) {
+		add_to_set(*self, &TABLES_KEY, index);
+	}
+
+	/// Registers the field `key` of the table at the given index as holding one of this module's C functions, leaving the stack unchanged.
+	///
+	/// When the module is unloaded by `#[gmod13_close]`, the field is replaced with a Lua function that raises an error (or does nothing, for `__gc`) if it still holds the function it holds now. Registering the same field again only updates the function it's expected to hold. Other fields of the table are left alone, so this is safe to use on shared tables such as `_G`.
+	///
+	/// The table isn't kept alive by this.
+	pub unsafe fn neutralize_field_on_unload(&self, index: i32, key: &str) {
+		let index = self.abs_index(index);
+		push_set(*self, &FIELDS_KEY);
+		self.push_value(index);
+		self.raw_get(-2);
+		if !self.is_table(-1) {
+			self.pop();
+			self.new_table();
+			self.push_value(index);
+			self.push_value(-2);
+			self.raw_set(-4);
+		}
+
+		self.push_string(key);
+		self.push_string(key);
+		self.raw_get(index);
+		self.raw_set(-3);
+		self.pop_n(2);
+	}
+}
+
+#[doc(hidden)]
+/// Called by `#[gmod13_close]`
+///
+/// Drops every Rust userdata that's still alive, neutralizes the C functions this module has given to Lua, and removes its metatables from the registry so that loading the module again creates new ones.
+pub unsafe fn __unload(lua: State) {
+	crate::userdata::finalize_all(lua);
+
+	lua.load_string(crate::lua_string!("")).unwrap();
+	let noop = lua.abs_index(-1);
+	lua.load_string(crate::lua_string!("error(\"this gmod-rs module has been unloaded\", 2)")).unwrap();
+	let unloaded = lua.abs_index(-1);
+
+	neutralize_tables(lua, noop, unloaded);
+	neutralize_fields(lua, noop, unloaded);
+	disconnect_wrappers(lua, unloaded);
+	forget_metatables(lua);
+
+	lua.pop_n(2);
+}
@@ -0,0 +1,288 @@
+use std::{ffi::c_void, fmt::Write};
+
+use crate::{lua::*, userdata::TaggedUserData};
+
+/// An owned snapshot of any Lua value.
+///
+/// This is the dynamic counterpart to `PushToLua`/`FromLua`: it can be read from any stack slot with `State::get_value` and pushed back with `PushToLua`.
+///
+/// Tables are copied recursively. Functions, userdata and threads are kept alive by a registry reference.
+#[derive(Clone)]
+pub enum Value {
+	Nil,
+	Bool(bool),
+	Number(LuaNumber),
+	String(Vec<u8>),
+
+	/// Key-value pairs, in the order `lua_next` visited them
+	Table(Vec<(Value, Value)>),
+
+	Function(LuaRef),
+
+	/// Full userdata. GMod's own userdata types (anything with a `MetaID` in its metatable) also carry their `TaggedUserData`.
+	UserData(LuaRef, Option<TaggedUserData>),
+
+	LightUserData(*mut c_void),
+	Thread(LuaRef),
+}
+
+impl Value {
+	/// Returns the Lua type name of this value, as `type()` would.
+	pub fn type_name(&self) -> &'static str {
+		match self {
+			Value::Nil => "nil",
+			Value::Bool(_) => "boolean",
+			Value::Number(_) => "number",
+			Value::String(_) => "string",
+			Value::Table(_) => "table",
+			Value::Function(_) => "function",
+			Value::UserData(..) | Value::LightUserData(_) => "userdata",
+			Value::Thread(_) => "thread",
+		}
+	}
+
+	#[inline(always)]
+	pub fn is_nil(&self) -> bool {
+		matches!(self, Value::Nil)
+	}
+
+	/// Pretty prints this value as a Lua literal.
+	///
+	/// Values without a literal form (functions, userdata and threads) are printed the way `tostring` would print them, so the output is only valid Lua for plain data.
+	pub fn to_lua_literal(&self) -> String {
+		let mut out = String::new();
+		self.write_lua_literal(&mut out).unwrap();
+		out
+	}
+
+	fn write_lua_literal<W: Write>(&self, f: &mut W) -> std::fmt::Result {
+		match self {
+			Value::Nil => f.write_str("nil"),
+			Value::Bool(bool) => write!(f, "{}", bool),
+			Value::Number(n) => write_lua_number(f, *n),
+			Value::String(bytes) => write_lua_string(f, bytes),
+			Value::Table(pairs) => {
+				if pairs.is_empty() {
+					return f.write_str("{}");
+				}
+
+				f.write_str("{ ")?;
+				let mut seq = 1.;
+				for (i, (k, v)) in pairs.iter().enumerate() {
+					if i != 0 {
+						f.write_str(", ")?;
+					}
+					match k {
+						Value::Number(n) if *n == seq => seq += 1.,
+						Value::String(key) if is_lua_identifier(key) => write!(f, "{} = ", String::from_utf8_lossy(key))?,
+						_ => {
+							f.write_char('[')?;
+							k.write_lua_literal(f)?;
+							f.write_str("] = ")?;
+						}
+					}
+					v.write_lua_literal(f)?;
+				}
+				f.write_str(" }")
+			},
+			Value::Function(r) => write!(f, "function: {:p}", r.as_ptr()),
+			Value::UserData(r, Some(tagged)) => write!(f, "{:?}: {:p}", tagged.resolved_type(), r.as_ptr()),
+			Value::UserData(r, None) => write!(f, "userdata: {:p}", r.as_ptr()),
+			Value::LightUserData(ptr) => write!(f, "userdata: {:p}", ptr),
+			Value::Thread(r) => write!(f, "thread: {:p}", r.as_ptr()),
+		}
+	}
+}
+
+fn write_lua_number<W: Write>(f: &mut W, n: LuaNumber) -> std::fmt::Result {
+	if n.is_nan() {
+		f.write_str("0/0")
+	} else if n.is_infinite() {
+		f.write_str(if n > 0. { "math.huge" } else { "-math.huge" })
+	} else if n.fract() == 0. && n.abs() < 1e15 {
+		write!(f, "{}", n as i64)
+	} else {
+		write!(f, "{:?}", n)
+	}
+}
+
+fn write_lua_string<W: Write>(f: &mut W, bytes: &[u8]) -> std::fmt::Result {
+	f.write_char('"')?;
+	for &byte in bytes {
+		match byte {
+			b'"' => f.write_str("\\\"")?,
+			b'\\' => f.write_str("\\\\")?,
+			b'\n' => f.write_str("\\n")?,
+			b'\r' => f.write_str("\\r")?,
+			b'\t' => f.write_str("\\t")?,
+			0x20..=0x7E => f.write_char(byte as char)?,
+			_ => write!(f, "\\{:03}", byte)?,
+		}
+	}
+	f.write_char('"')
+}
+
+fn is_lua_identifier(key: &[u8]) -> bool {
+	const KEYWORDS: &[&[u8]] = &[
+		b"and", b"break", b"do", b"else", b"elseif", b"end", b"false", b"for", b"function", b"if", b"in",
+		b"local", b"nil", b"not", b"or", b"repeat", b"return", b"then", b"true", b"until", b"while",
+		// GLua
+		b"continue",
+	];
+
+	match key.first() {
+		Some(first) if first.is_ascii_alphabetic() || *first == b'_' => {
+			key.iter().all(|c| c.is_ascii_alphanumeric() || *c == b'_') && !KEYWORDS.contains(&key)
+		},
+		_ => false,
+	}
+}
+
+impl std::fmt::Debug for Value {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Value::Nil => f.write_str("Nil"),
+			Value::Bool(bool) => f.debug_tuple("Bool").field(bool).finish(),
+			Value::Number(n) => f.debug_tuple("Number").field(n).finish(),
+			Value::String(bytes) => f.debug_tuple("String").field(&String::from_utf8_lossy(bytes)).finish(),
+			Value::Table(pairs) => f.debug_map().entries(pairs.iter().map(|(k, v)| (k, v))).finish(),
+			Value::Function(r) => f.debug_tuple("Function").field(r).finish(),
+			Value::UserData(r, tagged) => f.debug_tuple("UserData").field(r).field(tagged).finish(),
+			Value::LightUserData(ptr) => f.debug_tuple("LightUserData").field(ptr).finish(),
+			Value::Thread(r) => f.debug_tuple("Thread").field(r).finish(),
+		}
+	}
+}
+
+impl PartialEq for Value {
+	/// Tables are compared by their contents, regardless of order. Everything else is compared like `rawequal` would.
+	fn eq(&self, other: &Self) -> bool {
+		match (self, other) {
+			(Value::Nil, Value::Nil) => true,
+			(Value::Bool(a), Value::Bool(b)) => a == b,
+			(Value::Number(a), Value::Number(b)) => a == b,
+			(Value::String(a), Value::String(b)) => a == b,
+			(Value::Table(a), Value::Table(b)) => {
+				a.len() == b.len() && a.iter().all(|(ak, av)| b.iter().any(|(bk, bv)| ak == bk && av == bv))
+			},
+			(Value::Function(a), Value::Function(b)) => a == b,
+			(Value::UserData(a, _), Value::UserData(b, _)) => a == b,
+			(Value::LightUserData(a), Value::LightUserData(b)) => a == b,
+			(Value::Thread(a), Value::Thread(b)) => a == b,
+			_ => false,
+		}
+	}
+}
+
+impl Default for Value {
+	#[inline]
+	fn default() -> Self {
+		Value::Nil
+	}
+}
+
+impl PushToLua for &Value {
+	unsafe fn push_to_lua(self, lua: State) {
+		match self {
+			Value::Nil => lua.push_nil(),
+			Value::Bool(bool) => lua.push_boolean(*bool),
+			Value::Number(n) => lua.push_number(*n),
+			Value::String(bytes) => lua.push_binary_string(bytes),
+			Value::Table(pairs) => {
+				if !lua.check_stack(3) {
+					lua.error("stack overflow while pushing table");
+				}
+				lua.create_table(0, pairs.len() as i32);
+				for (k, v) in pairs {
+					k.push_to_lua(lua);
+					v.push_to_lua(lua);
+					lua.set_table(-3);
+				}
+			},
+			Value::Function(r) | Value::UserData(r, _) | Value::Thread(r) => r.push(lua),
+			Value::LightUserData(ptr) => lua.push_lightuserdata(*ptr),
+		}
+	}
+}
+impl PushToLua for Value {
+	#[inline]
+	unsafe fn push_to_lua(self, lua: State) {
+		(&self).push_to_lua(lua)
+	}
+}
+
+impl FromLua for Value {
+	#[inline]
+	unsafe fn from_lua(lua: State, index: i32) -> Result<Self, FromLuaError> {
+		Ok(lua.get_value(index))
+	}
+}
+
+impl State {
+	/// Takes an owned snapshot of the value at the given index of the stack. See `Value`.
+	///
+	/// Tables that contain themselves are copied up to the first repeat, which reads as `Value::Nil`.
+	pub unsafe fn get_value(&self, index: i32) -> Value {
+		read_value(*self, self.abs_index(index), &mut Vec::new())
+	}
+}
+
+unsafe fn read_value(lua: State, index: i32, seen: &mut Vec<*const c_void>) -> Value {
+	match lua.type_of(index) {
+		LuaType::Boolean => Value::Bool(lua.get_boolean(index)),
+		LuaType::Number => Value::Number(lua.to_number(index)),
+		LuaType::String => Value::String(lua.get_binary_string(index).unwrap_or_default().to_vec()),
+		LuaType::Table => read_table(lua, index, seen),
+		LuaType::Function => Value::Function(LuaRef::from_stack(lua, index)),
+		LuaType::UserData => Value::UserData(LuaRef::from_stack(lua, index), read_tagged_userdata(lua, index)),
+		LuaType::LightUserData => Value::LightUserData(lua.to_userdata(index)),
+		LuaType::Thread => Value::Thread(LuaRef::from_stack(lua, index)),
+		LuaType::Nil | LuaType::None => Value::Nil,
+	}
+}
+
+unsafe fn read_table(lua: State, index: i32, seen: &mut Vec<*const c_void>) -> Value {
+	let ptr = lua.to_pointer(index);
+	if seen.contains(&ptr) {
+		return Value::Nil;
+	}
+
+	if !lua.check_stack(3) {
+		lua.error("stack overflow while reading table");
+	}
+
+	seen.push(ptr);
+
+	let mut pairs = Vec::new();
+	lua.push_nil();
+	while lua.next(index) != 0 {
+		let top = lua.get_top();
+		let k = read_value(lua, top - 1, seen);
+		let v = read_value(lua, top, seen);
+		lua.pop();
+
+		if !k.is_nil() {
+			pairs.push((k, v));
+		}
+	}
+
+	seen.pop();
+
+	Value::Table(pairs)
+}
+
+pub(crate) unsafe fn read_tagged_userdata(lua: State, index: i32) -> Option<TaggedUserData> {
+	if lua.get_metatable(index) == 0 {
+		return None;
+	}
+
+	lua.get_field(-1, crate::lua_string!("MetaID"));
+	let tagged = if lua.is_number(-1) {
+		Some(*(lua.to_userdata(index) as *const TaggedUserData))
+	} else {
+		None
+	};
+	lua.pop_n(2);
+
+	tagged
+}
@@ -0,0 +1,247 @@
+use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+
+use super::Vector;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd)]
+#[repr(C)]
+pub struct Angle {
+	pub p: f32,
+	pub y: f32,
+	pub r: f32
+}
+
+#[inline]
+/// Sine and cosine of an angle in degrees, converted to radians in single precision like the engine's `DEG2RAD`.
+fn sin_cos(degrees: f32) -> (f32, f32) {
+	(degrees * (std::f32::consts::PI / 180.0)).sin_cos()
+}
+
+#[inline]
+/// The engine's `AngleNormalize`
+fn normalize_component(angle: f32) -> f32 {
+	let mut angle = angle % 360.0;
+	if angle > 180.0 {
+		angle -= 360.0;
+	}
+	if angle < -180.0 {
+		angle += 360.0;
+	}
+	angle
+}
+
+impl Angle {
+	pub const ZERO: Angle = Angle::new(0.0, 0.0, 0.0);
+
+	#[inline]
+	pub const fn new(p: f32, y: f32, r: f32) -> Angle {
+		Angle { p, y, r }
+	}
+
+	/// The rows of the rotation matrix for this angle, as computed by the engine's `AngleMatrix`.
+	pub(crate) fn matrix(&self) -> [Vector; 3] {
+		let (sp, cp) = sin_cos(self.p);
+		let (sy, cy) = sin_cos(self.y);
+		let (sr, cr) = sin_cos(self.r);
+
+		let crcy = cr * cy;
+		let crsy = cr * sy;
+		let srcy = sr * cy;
+		let srsy = sr * sy;
+
+		[
+			Vector::new(cp * cy, sp * srcy - crsy, sp * crcy + srsy),
+			Vector::new(cp * sy, sp * srsy + crcy, sp * crsy - srcy),
+			Vector::new(-sp, sr * cp, cr * cp),
+		]
+	}
+
+	/// `Angle:Forward()`
+	pub fn forward(&self) -> Vector {
+		let (sp, cp) = sin_cos(self.p);
+		let (sy, cy) = sin_cos(self.y);
+		Vector::new(cp * cy, cp * sy, -sp)
+	}
+
+	/// `Angle:Right()`
+	pub fn right(&self) -> Vector {
+		let (sp, cp) = sin_cos(self.p);
+		let (sy, cy) = sin_cos(self.y);
+		let (sr, cr) = sin_cos(self.r);
+		Vector::new(
+			-sr * sp * cy + -cr * -sy,
+			-sr * sp * sy + -cr * cy,
+			-sr * cp,
+		)
+	}
+
+	/// `Angle:Up()`
+	pub fn up(&self) -> Vector {
+		let (sp, cp) = sin_cos(self.p);
+		let (sy, cy) = sin_cos(self.y);
+		let (sr, cr) = sin_cos(self.r);
+		Vector::new(
+			cr * sp * cy + -sr * -sy,
+			cr * sp * sy + -sr * cy,
+			cr * cp,
+		)
+	}
+
+	#[inline]
+	/// `Angle:Normalize()`
+	///
+	/// Wraps every component into the range [-180, 180].
+	pub fn normalize(&mut self) {
+		*self = self.normalized();
+	}
+
+	#[inline]
+	#[must_use]
+	/// See `normalize`
+	pub fn normalized(&self) -> Angle {
+		Angle::new(normalize_component(self.p), normalize_component(self.y), normalize_component(self.r))
+	}
+
+	#[inline]
+	/// `Angle:IsZero()`
+	pub fn is_zero(&self) -> bool {
+		self.p == 0.0 && self.y == 0.0 && self.r == 0.0
+	}
+
+	#[inline]
+	/// `Angle:IsEqualTol(other, tolerance)`
+	pub fn is_equal_tol(&self, other: Angle, tolerance: f32) -> bool {
+		(self.p - other.p).abs() <= tolerance
+			&& (self.y - other.y).abs() <= tolerance
+			&& (self.r - other.r).abs() <= tolerance
+	}
+
+	#[inline]
+	/// `LerpAngle(t, self, to)`
+	pub fn lerp(&self, to: Angle, t: f32) -> Angle {
+		*self + (to - *self) * t
+	}
+}
+
+impl Add for Angle {
+	type Output = Angle;
+
+	#[inline]
+	fn add(self, rhs: Angle) -> Angle {
+		Angle::new(self.p + rhs.p, self.y + rhs.y, self.r + rhs.r)
+	}
+}
+impl Sub for Angle {
+	type Output = Angle;
+
+	#[inline]
+	fn sub(self, rhs: Angle) -> Angle {
+		Angle::new(self.p - rhs.p, self.y - rhs.y, self.r - rhs.r)
+	}
+}
+impl Mul<f32> for Angle {
+	type Output = Angle;
+
+	#[inline]
+	fn mul(self, rhs: f32) -> Angle {
+		Angle::new(self.p * rhs, self.y * rhs, self.r * rhs)
+	}
+}
+impl Mul<Angle> for f32 {
+	type Output = Angle;
+
+	#[inline]
+	fn mul(self, rhs: Angle) -> Angle {
+		rhs * self
+	}
+}
+impl Div<f32> for Angle {
+	type Output = Angle;
+
+	#[inline]
+	/// Multiplies by the reciprocal, like the engine does.
+	fn div(self, rhs: f32) -> Angle {
+		self * (1.0 / rhs)
+	}
+}
+impl Neg for Angle {
+	type Output = Angle;
+
+	#[inline]
+	fn neg(self) -> Angle {
+		Angle::new(-self.p, -self.y, -self.r)
+	}
+}
+
+impl AddAssign for Angle {
+	#[inline]
+	fn add_assign(&mut self, rhs: Angle) {
+		*self = *self + rhs;
+	}
+}
+impl SubAssign for Angle {
+	#[inline]
+	fn sub_assign(&mut self, rhs: Angle) {
+		*self = *self - rhs;
+	}
+}
+impl MulAssign<f32> for Angle {
+	#[inline]
+	fn mul_assign(&mut self, rhs: f32) {
+		*self = *self * rhs;
+	}
+}
+impl DivAssign<f32> for Angle {
+	#[inline]
+	fn div_assign(&mut self, rhs: f32) {
+		*self = *self / rhs;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn angle_vectors() {
+		let zero = Angle::ZERO;
+		assert_eq!(zero.forward(), Vector::new(1.0, 0.0, 0.0));
+		assert_eq!(zero.right(), Vector::new(0.0, -1.0, 0.0));
+		assert_eq!(zero.up(), Vector::new(0.0, 0.0, 1.0));
+
+		let yaw = Angle::new(0.0, 90.0, 0.0);
+		assert!(yaw.forward().is_equal_tol(Vector::new(0.0, 1.0, 0.0), 1e-6));
+		assert!(yaw.right().is_equal_tol(Vector::new(1.0, 0.0, 0.0), 1e-6));
+		assert!(yaw.up().is_equal_tol(Vector::new(0.0, 0.0, 1.0), 1e-6));
+
+		let pitch = Angle::new(45.0, 0.0, 0.0);
+		assert!(pitch.forward().is_equal_tol(Vector::new(0.70710677, 0.0, -0.70710677), 1e-6));
+		assert!(pitch.up().is_equal_tol(Vector::new(0.70710677, 0.0, 0.70710677), 1e-6));
+
+		let roll = Angle::new(0.0, 0.0, 90.0);
+		assert!(roll.right().is_equal_tol(Vector::new(0.0, 0.0, -1.0), 1e-6));
+		assert!(roll.up().is_equal_tol(Vector::new(0.0, -1.0, 0.0), 1e-6));
+	}
+
+	#[test]
+	fn angle_vectors_match_matrix() {
+		let angle = Angle::new(12.0, 34.0, 56.0);
+		let matrix = angle.matrix();
+		let [x, y, z] = matrix;
+		assert!(angle.forward().is_equal_tol(Vector::new(x.x, y.x, z.x), 1e-6));
+		assert!(angle.right().is_equal_tol(-Vector::new(x.y, y.y, z.y), 1e-6));
+		assert!(angle.up().is_equal_tol(Vector::new(x.z, y.z, z.z), 1e-6));
+	}
+
+	#[test]
+	fn angle_normalize() {
+		assert_eq!(normalize_component(190.0), -170.0);
+		assert_eq!(normalize_component(-190.0), 170.0);
+		assert_eq!(normalize_component(180.0), 180.0);
+		assert_eq!(normalize_component(-180.0), -180.0);
+		assert_eq!(normalize_component(360.0), 0.0);
+		assert_eq!(normalize_component(540.0), 180.0);
+		assert_eq!(normalize_component(720.5), 0.5);
+		assert_eq!(normalize_component(-370.0), -10.0);
+		assert_eq!(Angle::new(370.0, -350.0, 200.0).normalized(), Angle::new(10.0, 10.0, -160.0));
+	}
+}
@@ -0,0 +1,157 @@
+use std::{cell::RefCell, collections::HashMap, ffi::c_void, marker::PhantomData, ops::{Deref, DerefMut}};
+
+use super::header::{self, RustUserDataHeader};
+
+/// Borrow state of a userdata: `0` when unborrowed, the number of shared borrows when positive, and `-1` while mutably borrowed.
+pub(crate) type BorrowState = isize;
+
+thread_local! {
+	/// Borrow states of engine userdata, which have no header of ours to keep them in.
+	static ENGINE_BORROWS: RefCell<HashMap<*mut c_void, BorrowState>> = RefCell::new(HashMap::new());
+}
+
+/// Where a userdata's borrow state lives.
+pub(crate) enum BorrowFlag {
+	/// In the `RustUserDataHeader`
+	Header(*mut RustUserDataHeader),
+
+	/// In `ENGINE_BORROWS`, keyed by the userdata's data pointer
+	Engine(*mut c_void),
+}
+impl BorrowFlag {
+	unsafe fn get(&self) -> BorrowState {
+		match self {
+			BorrowFlag::Header(header) => header::borrow_state(*header),
+			BorrowFlag::Engine(key) => ENGINE_BORROWS.with(|borrows| borrows.borrow().get(key).copied().unwrap_or(0)),
+		}
+	}
+
+	unsafe fn set(&self, state: BorrowState) {
+		match self {
+			BorrowFlag::Header(header) => header::set_borrow_state(*header, state),
+			BorrowFlag::Engine(key) => ENGINE_BORROWS.with(|borrows| {
+				let mut borrows = borrows.borrow_mut();
+				if state == 0 {
+					borrows.remove(key);
+				} else {
+					borrows.insert(*key, state);
+				}
+			}),
+		}
+	}
+
+	/// Adds a shared borrow, failing if it's mutably borrowed.
+	pub(crate) unsafe fn borrow(&self) -> Result<(), BorrowError> {
+		match self.get() {
+			state if state < 0 => Err(BorrowError::AlreadyMutablyBorrowed),
+			state => {
+				self.set(state + 1);
+				Ok(())
+			}
+		}
+	}
+
+	/// Adds a mutable borrow, failing if it's borrowed at all.
+	pub(crate) unsafe fn borrow_mut(&self) -> Result<(), BorrowError> {
+		match self.get() {
+			0 => {
+				self.set(-1);
+				Ok(())
+			},
+			_ => Err(BorrowError::AlreadyBorrowed),
+		}
+	}
+}
+
+/// A userdata couldn't be borrowed because it's already in use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BorrowError {
+	/// It's borrowed, so it can't be borrowed mutably
+	AlreadyBorrowed,
+
+	/// It's mutably borrowed, so it can't be borrowed at all
+	AlreadyMutablyBorrowed,
+}
+impl std::fmt::Display for BorrowError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			BorrowError::AlreadyBorrowed => f.write_str("already borrowed"),
+			BorrowError::AlreadyMutablyBorrowed => f.write_str("already mutably borrowed"),
+		}
+	}
+}
+impl std::error::Error for BorrowError {}
+
+/// A shared borrow of the value of a userdata, like `std::cell::Ref`.
+///
+/// The userdata can't be mutably borrowed until this is dropped. If a Rust userdata is garbage collected while it's borrowed, its value is only dropped once every borrow has been released.
+pub struct UserDataRef<'a, T: ?Sized> {
+	ptr: *const T,
+	flag: BorrowFlag,
+	_phantom: PhantomData<&'a T>,
+}
+impl<T: ?Sized> UserDataRef<'_, T> {
+	/// Takes a shared borrow of `ptr`, which `flag` tracks.
+	pub(crate) unsafe fn new(ptr: *const T, flag: BorrowFlag) -> Result<Self, BorrowError> {
+		flag.borrow()?;
+		Ok(UserDataRef { ptr, flag, _phantom: PhantomData })
+	}
+}
+impl<T: ?Sized> Deref for UserDataRef<'_, T> {
+	type Target = T;
+
+	#[inline]
+	fn deref(&self) -> &T {
+		unsafe { &*self.ptr }
+	}
+}
+impl<T: ?Sized> Drop for UserDataRef<'_, T> {
+	fn drop(&mut self) {
+		unsafe { self.flag.set(self.flag.get() - 1) }
+	}
+}
+impl<T: ?Sized + std::fmt::Debug> std::fmt::Debug for UserDataRef<'_, T> {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		(**self).fmt(f)
+	}
+}
+
+/// A mutable borrow of the value of a userdata, like `std::cell::RefMut`.
+///
+/// The userdata can't be borrowed again until this is dropped, so Lua calling back into a method of an object that's already being mutated is caught rather than aliasing.
+pub struct UserDataRefMut<'a, T: ?Sized> {
+	ptr: *mut T,
+	flag: BorrowFlag,
+	_phantom: PhantomData<&'a mut T>,
+}
+impl<T: ?Sized> UserDataRefMut<'_, T> {
+	/// Takes a mutable borrow of `ptr`, which `flag` tracks.
+	pub(crate) unsafe fn new(ptr: *mut T, flag: BorrowFlag) -> Result<Self, BorrowError> {
+		flag.borrow_mut()?;
+		Ok(UserDataRefMut { ptr, flag, _phantom: PhantomData })
+	}
+}
+impl<T: ?Sized> Deref for UserDataRefMut<'_, T> {
+	type Target = T;
+
+	#[inline]
+	fn deref(&self) -> &T {
+		unsafe { &*self.ptr }
+	}
+}
+impl<T: ?Sized> DerefMut for UserDataRefMut<'_, T> {
+	#[inline]
+	fn deref_mut(&mut self) -> &mut T {
+		unsafe { &mut *self.ptr }
+	}
+}
+impl<T: ?Sized> Drop for UserDataRefMut<'_, T> {
+	fn drop(&mut self) {
+		unsafe { self.flag.set(0) }
+	}
+}
+impl<T: ?Sized + std::fmt::Debug> std::fmt::Debug for UserDataRefMut<'_, T> {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		(**self).fmt(f)
+	}
+}
@@ -0,0 +1,233 @@
+use std::{ffi::CStr, marker::PhantomData};
+
+use crate::lua::{LuaFunction, State};
+
+use super::{UserDataRef, UserDataRefMut};
+
+/// A Rust type that can be exposed to Lua as userdata with its own methods and fields.
+///
+/// Usually implemented with the `#[lua_class]` and `#[lua_methods]` attribute macros.
+///
+/// The metatable is created the first time a value of this type is pushed, and stored in the registry under a name made from `NAME` and this module's address, so that other gmod-rs modules with a type of the same name get their own metatable. `NAME` itself is what Lua sees, as `__name` and `MetaName`, and is used in error messages.
+///
+/// # Example
+///
+/// ```ignore
+/// #[lua_class]
+/// struct Counter {
+///     #[lua(get, set)]
+///     count: i32,
+/// }
+///
+/// #[lua_methods]
+/// impl Counter {
+///     #[lua(name = "Increment")]
+///     fn increment(&mut self, lua: gmod::lua::State) {
+///         self.count += 1;
+///     }
+/// }
+///
+/// lua.push_userdata(Counter { count: 0 });
+/// ```
+pub trait LuaUserData: Sized + 'static {
+	/// The name of this type in Lua.
+	const NAME: &'static CStr;
+
+	/// Adds methods (and metamethods) to this type's metatable.
+	fn register_methods(_methods: &mut UserDataMethods<Self>) {}
+
+	/// Adds field getters and setters to this type's metatable.
+	fn register_fields(_fields: &mut UserDataFields<Self>) {}
+}
+
+#[doc(hidden)]
+/// Fallback used by `#[lua_class]` when there is no `#[lua_methods]` block for the type.
+///
+/// Inherent associated functions take priority over trait ones, so the function generated by `#[lua_methods]` wins when it exists.
+pub trait __LuaClassNoMethods: LuaUserData {
+	#[inline(always)]
+	fn __gmod_register_methods(_methods: &mut UserDataMethods<Self>) {}
+}
+impl<T: LuaUserData> __LuaClassNoMethods for T {}
+
+/// Methods and metamethods of a `LuaUserData` type. See `LuaUserData::register_methods`.
+pub struct UserDataMethods<T: LuaUserData> {
+	methods: Vec<(&'static str, LuaFunction)>,
+	meta: Vec<(&'static str, LuaFunction)>,
+	_phantom: PhantomData<T>,
+}
+impl<T: LuaUserData> UserDataMethods<T> {
+	/// Adds a method, available through `__index`. The userdata is passed as the first argument when called with `obj:Method()`.
+	pub fn add(&mut self, name: &'static str, func: LuaFunction) -> &mut Self {
+		self.methods.push((name, func));
+		self
+	}
+
+	/// Sets a metamethod, such as `__tostring` or `__eq`, directly on the metatable.
+	pub fn add_meta(&mut self, name: &'static str, func: LuaFunction) -> &mut Self {
+		self.meta.push((name, func));
+		self
+	}
+}
+
+/// Field getters and setters of a `LuaUserData` type. See `LuaUserData::register_fields`.
+pub struct UserDataFields<T: LuaUserData> {
+	getters: Vec<(&'static str, LuaFunction)>,
+	setters: Vec<(&'static str, LuaFunction)>,
+	_phantom: PhantomData<T>,
+}
+impl<T: LuaUserData> UserDataFields<T> {
+	/// Adds a getter for `obj.name`. It's called with the userdata as its only argument, and should return one value.
+	pub fn add_getter(&mut self, name: &'static str, func: LuaFunction) -> &mut Self {
+		self.getters.push((name, func));
+		self
+	}
+
+	/// Adds a setter for `obj.name = value`. It's called with the userdata and the new value.
+	pub fn add_setter(&mut self, name: &'static str, func: LuaFunction) -> &mut Self {
+		self.setters.push((name, func));
+		self
+	}
+}
+
+/// `__index` for types with field getters. Upvalue 1 is the methods table, upvalue 2 is the getters table.
+unsafe extern "C-unwind" fn __index(lua: State) -> i32 {
+	lua.push_value(2);
+	lua.get_table(lua.upvalue_index(1));
+	if !lua.is_nil(-1) {
+		return 1;
+	}
+	lua.pop();
+
+	lua.push_value(2);
+	lua.get_table(lua.upvalue_index(2));
+	if lua.is_nil(-1) {
+		return 1;
+	}
+	lua.push_value(1);
+	lua.call(1, 1);
+	1
+}
+
+/// `__newindex` for types with field setters. Upvalue 1 is the setters table, upvalue 2 is the type's name.
+unsafe extern "C-unwind" fn __newindex(lua: State) -> i32 {
+	lua.push_value(2);
+	lua.get_table(lua.upvalue_index(1));
+	if lua.is_nil(-1) {
+		let key = lua.dump_val(2);
+		let name = lua.get_string(lua.upvalue_index(2)).unwrap_or_default().into_owned();
+		lua.error(format!("cannot set field {} of {}", key, name));
+	}
+	lua.push_value(1);
+	lua.push_value(3);
+	lua.call(2, 0);
+	0
+}
+
+impl State {
+	/// Pushes the metatable of `T` onto the stack, creating it first if this is the first time it's been used.
+	pub unsafe fn push_class_metatable<T: LuaUserData>(&self) {
+		super::metatable::with_name::<T, _>(true, || format!("class {}", T::NAME.to_string_lossy()), |name| {
+			if !self.new_metatable(name.as_ptr()) {
+				crate::lua::forget_metatable_on_unload(*self, name);
+				build_class_metatable::<T>(*self);
+			}
+		})
+	}
+
+	/// Moves `data` into a new userdata with `T`'s metatable and pushes it onto the stack.
+	pub unsafe fn push_userdata<T: LuaUserData>(&self, data: T) -> *mut T {
+		let ptr = super::header::alloc(*self, data);
+		self.push_class_metatable::<T>();
+		self.set_metatable(-2);
+		ptr
+	}
+
+	/// Checks that argument `arg` is a `T` created by `push_userdata`, and borrows it. Raises a Lua error otherwise, or if it's currently mutably borrowed.
+	#[inline]
+	pub unsafe fn check_class<T: LuaUserData>(&self, arg: i32) -> UserDataRef<'_, T> {
+		match super::header::borrow(self, arg) {
+			Ok(borrow) => borrow,
+			Err(err) => self.arg_error(arg, super::header::arg_error_message(&T::NAME.to_string_lossy(), err)),
+		}
+	}
+
+	/// Like `check_class`, but borrows mutably. Raises a Lua error if it's currently borrowed at all, such as when Lua calls back into a `&mut self` method that's already running.
+	#[inline]
+	pub unsafe fn check_class_mut<T: LuaUserData>(&self, arg: i32) -> UserDataRefMut<'_, T> {
+		match super::header::borrow_mut(self, arg) {
+			Ok(borrow) => borrow,
+			Err(err) => self.arg_error(arg, super::header::arg_error_message(&T::NAME.to_string_lossy(), err)),
+		}
+	}
+
+	/// Borrows the `T` at the given index, if it is one created by `push_userdata` and isn't mutably borrowed.
+	#[inline]
+	pub unsafe fn test_class<T: LuaUserData>(&self, index: i32) -> Option<UserDataRef<'_, T>> {
+		self.try_rust_userdata::<T>(index).ok()
+	}
+}
+
+/// Fills in the freshly created metatable at the top of the stack.
+unsafe fn build_class_metatable<T: LuaUserData>(lua: State) {
+	let mut methods = UserDataMethods::<T> { methods: Vec::new(), meta: Vec::new(), _phantom: PhantomData };
+	T::register_methods(&mut methods);
+
+	let mut fields = UserDataFields::<T> { getters: Vec::new(), setters: Vec::new(), _phantom: PhantomData };
+	T::register_fields(&mut fields);
+
+	let type_name = T::NAME.to_string_lossy();
+	lua.push_string(&type_name);
+	lua.set_field(-2, crate::lua_string!("__name"));
+	lua.push_string(&type_name);
+	lua.set_field(-2, crate::lua_string!("MetaName"));
+
+	lua.neutralize_on_unload(-1);
+
+	lua.create_table(0, methods.methods.len() as i32);
+	lua.neutralize_on_unload(-1);
+	for (name, func) in methods.methods {
+		lua.push_function(func);
+		set_field_str(lua, -2, name);
+	}
+	if fields.getters.is_empty() {
+		lua.set_field(-2, crate::lua_string!("__index"));
+	} else {
+		lua.create_table(0, fields.getters.len() as i32);
+		for (name, func) in fields.getters {
+			lua.push_function(func);
+			set_field_str(lua, -2, name);
+		}
+		lua.push_closure(__index, 2);
+		lua.set_field(-2, crate::lua_string!("__index"));
+	}
+
+	if !fields.setters.is_empty() {
+		lua.create_table(0, fields.setters.len() as i32);
+		for (name, func) in fields.setters {
+			lua.push_function(func);
+			set_field_str(lua, -2, name);
+		}
+		lua.push_string(&type_name);
+		lua.push_closure(__newindex, 2);
+		lua.set_field(-2, crate::lua_string!("__newindex"));
+	}
+
+	if std::mem::needs_drop::<T>() {
+		lua.push_function(super::__gc::<T>);
+		lua.set_field(-2, crate::lua_string!("__gc"));
+	}
+
+	for (name, func) in methods.meta {
+		lua.push_function(func);
+		set_field_str(lua, -2, name);
+	}
+}
+
+/// `set_field` for keys that aren't NUL terminated.
+unsafe fn set_field_str(lua: State, index: i32, k: &str) {
+	let index = lua.abs_index(index);
+	lua.push_string(k);
+	lua.insert(-2);
+	lua.set_table(index);
+}
@@ -0,0 +1,102 @@
+use std::ffi::c_void;
+
+use crate::lua::{self, FromLua, FromLuaError, LuaString, LuaType, PushToLua, State, LUA_REGISTRYINDEX};
+
+use super::{Angle, Vector};
+
+/// Registry keys for the cached engine constructors. Only their addresses are used.
+static VECTOR_CONSTRUCTOR: u8 = 0;
+static ANGLE_CONSTRUCTOR: u8 = 0;
+
+/// Pushes the global function `name`, caching it in the registry under `key` the first time it's used.
+///
+/// Caching means addons that later override the global can't change what we push. Raises a Lua error if the global isn't a function, such as in a menu state.
+unsafe fn push_constructor(lua: State, key: &'static u8, name: LuaString) {
+	lua.push_lightuserdata(key as *const u8 as *mut c_void);
+	lua.get_table(LUA_REGISTRYINDEX);
+	if lua.is_function(-1) {
+		return;
+	}
+	lua.pop();
+
+	lua.get_global(name);
+	if !lua.is_function(-1) {
+		lua.pop();
+		let name = std::ffi::CStr::from_ptr(name).to_string_lossy();
+		lua.error(format!("can't push a {0}: the {0} global is missing", name));
+	}
+	lua.push_lightuserdata(key as *const u8 as *mut c_void);
+	lua.push_value(-2);
+	lua.set_table(LUA_REGISTRYINDEX);
+}
+
+/// Reads the engine userdata at `index` if it's a `T`.
+unsafe fn read_engine_userdata<T: Copy + super::CoercibleUserData>(lua: State, index: i32) -> Option<T> {
+	let tagged = lua::read_tagged_userdata(lua, index)?;
+	let value = tagged.borrow::<T>().ok().map(|value| *value);
+	value
+}
+
+/// Reads three numbers, pushing each one with `push`. Fails if any of them isn't a number.
+unsafe fn read_numbers(lua: State, mut push: impl FnMut(usize)) -> Option<[f32; 3]> {
+	let mut out = [0.0; 3];
+	for (i, out) in out.iter_mut().enumerate() {
+		push(i);
+		let n = if lua.is_number(-1) { Some(lua.to_number(-1) as f32) } else { None };
+		lua.pop();
+		*out = n?;
+	}
+	Some(out)
+}
+
+/// Reads the `{a = ..., b = ..., c = ...}` fields of the table at `index`, or failing that the `{..., ..., ...}` in its array part.
+unsafe fn read_table_fields(lua: State, index: i32, fields: [LuaString; 3]) -> Option<[f32; 3]> {
+	let index = lua.abs_index(index);
+	read_numbers(lua, |i| lua.get_field(index, fields[i])).or_else(|| read_numbers(lua, |i| lua.raw_geti(index, i as i32 + 1)))
+}
+
+impl PushToLua for Vector {
+	/// Pushes a new engine `Vector` by calling the `Vector` global.
+	unsafe fn push_to_lua(self, lua: State) {
+		push_constructor(lua, &VECTOR_CONSTRUCTOR, crate::lua_string!("Vector"));
+		lua.push_number(self.x as _);
+		lua.push_number(self.y as _);
+		lua.push_number(self.z as _);
+		lua.call(3, 1);
+	}
+}
+
+impl PushToLua for Angle {
+	/// Pushes a new engine `Angle` by calling the `Angle` global.
+	unsafe fn push_to_lua(self, lua: State) {
+		push_constructor(lua, &ANGLE_CONSTRUCTOR, crate::lua_string!("Angle"));
+		lua.push_number(self.p as _);
+		lua.push_number(self.y as _);
+		lua.push_number(self.r as _);
+		lua.call(3, 1);
+	}
+}
+
+impl FromLua for Vector {
+	/// Reads an engine `Vector`, or a table with numeric `x`, `y` and `z` fields or three numbers in its array part.
+	unsafe fn from_lua(lua: State, index: i32) -> Result<Self, FromLuaError> {
+		let vector = match lua.type_of(index) {
+			LuaType::UserData => read_engine_userdata(lua, index),
+			LuaType::Table => read_table_fields(lua, index, [crate::lua_string!("x"), crate::lua_string!("y"), crate::lua_string!("z")]).map(|[x, y, z]| Vector::new(x, y, z)),
+			_ => None,
+		};
+		vector.ok_or_else(|| FromLuaError::type_mismatch(lua, index, "Vector"))
+	}
+}
+
+impl FromLua for Angle {
+	/// Reads an engine `Angle`, or a table with numeric `p`, `y` and `r` fields or three numbers in its array part.
+	unsafe fn from_lua(lua: State, index: i32) -> Result<Self, FromLuaError> {
+		let angle = match lua.type_of(index) {
+			LuaType::UserData => read_engine_userdata(lua, index),
+			LuaType::Table => read_table_fields(lua, index, [crate::lua_string!("p"), crate::lua_string!("y"), crate::lua_string!("r")]).map(|[p, y, r]| Angle::new(p, y, r)),
+			_ => None,
+		};
+		angle.ok_or_else(|| FromLuaError::type_mismatch(lua, index, "Angle"))
+	}
+}
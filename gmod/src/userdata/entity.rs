@@ -0,0 +1,198 @@
+use crate::lua::{self, FromLua, FromLuaError, FromLuaMulti, LuaError, PushToLua, PushToLuaMulti, State, LUA_OK};
+
+use super::UserData;
+
+/// A handle to an entity that's safe to keep across ticks, like the engine's `EHANDLE`.
+///
+/// It stores the entity's index and creation ID rather than the Lua object, and re-resolves it through `Entity(index)` every time it's used. If the entity has been removed, or its index has been reused by a new entity, the handle is no longer valid.
+///
+/// `Entity:GetCreationID()` only exists serverside, so clientside handles only store the index and can't tell when it's been reused. Clientside-only entities have no index at all, so their handles are never valid.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Entity {
+	index: i32,
+
+	/// `None` clientside, where `GetCreationID` doesn't exist
+	serial: Option<u32>,
+}
+
+/// An error from calling a method on an `Entity`.
+#[derive(Debug, Clone)]
+pub enum EntityError {
+	/// The entity has been removed, or was never valid
+	Invalid,
+
+	/// The method raised an error
+	Lua(LuaError),
+
+	/// The method returned something unexpected
+	Return(FromLuaError),
+}
+impl std::fmt::Display for EntityError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			EntityError::Invalid => f.write_str("tried to use a NULL entity!"),
+			EntityError::Lua(LuaError::RuntimeError(Some(msg))) => f.write_str(msg),
+			EntityError::Lua(err) => write!(f, "{:?}", err),
+			EntityError::Return(err) => write!(f, "bad return value ({})", err),
+		}
+	}
+}
+impl std::error::Error for EntityError {}
+impl From<FromLuaError> for EntityError {
+	#[inline]
+	fn from(err: FromLuaError) -> Self {
+		EntityError::Return(err)
+	}
+}
+
+/// Calls `object:method(...)` on the value at `index`.
+unsafe fn call_method<Args: PushToLuaMulti, Ret: FromLuaMulti>(lua: State, index: i32, method: &str, args: Args) -> Result<Ret, EntityError> {
+	let index = lua.abs_index(index);
+	let base = lua.get_top();
+
+	lua.push_string(method);
+	lua.get_table(index);
+	lua.push_value(index);
+	let nargs = args.push_to_lua_multi(lua);
+
+	let code = lua.pcall(nargs + 1, Ret::COUNT, 0);
+	if code != LUA_OK {
+		let err = LuaError::from_lua_state(lua, code);
+		lua.pop();
+		return Err(EntityError::Lua(err));
+	}
+
+	let ret = Ret::from_lua_multi(lua, base + 1);
+	lua.set_top(base);
+	Ok(ret?)
+}
+
+/// Whether the entity at `index` exists. The world isn't `IsValid`, but it still counts.
+unsafe fn entity_exists(lua: State, index: i32) -> bool {
+	matches!(call_method(lua, index, "IsValid", ()), Ok(true)) || matches!(call_method(lua, index, "IsWorld", ()), Ok(true))
+}
+
+/// Returns the creation ID of the entity at `index`, if `GetCreationID` exists in this realm.
+unsafe fn creation_id(lua: State, index: i32) -> Option<u32> {
+	let index = lua.abs_index(index);
+	lua.get_field(index, crate::lua_string!("GetCreationID"));
+	let exists = lua.is_function(-1);
+	lua.pop();
+
+	if exists {
+		call_method::<_, f64>(lua, index, "GetCreationID", ()).ok().map(|id| id as u32)
+	} else {
+		None
+	}
+}
+
+impl Entity {
+	/// A handle that never refers to an entity.
+	pub const NULL: Entity = Entity { index: -1, serial: None };
+
+	#[inline]
+	/// The entity's index, as returned by `Entity:EntIndex()`. `-1` for `Entity::NULL`.
+	pub fn index(&self) -> i32 {
+		self.index
+	}
+
+	#[inline]
+	/// The entity's creation ID when the handle was made, as returned by `Entity:GetCreationID()`, which tells apart entities that reuse the same index.
+	///
+	/// `GetCreationID` only exists serverside, so this is always `None` clientside (and for `Entity::NULL`).
+	pub fn serial(&self) -> Option<u32> {
+		self.serial
+	}
+
+	/// Pushes the entity with this handle's index and checks that it's still the same entity.
+	///
+	/// Returns `false` and leaves nothing on the stack if it isn't.
+	unsafe fn resolve(&self, lua: State) -> bool {
+		if self.index < 0 {
+			return false;
+		}
+
+		lua.get_global(crate::lua_string!("Entity"));
+		lua.push_integer(self.index as _);
+		if lua.pcall(1, 1, 0) != LUA_OK {
+			lua.pop();
+			return false;
+		}
+
+		if entity_exists(lua, -1) && (self.serial.is_none() || creation_id(lua, -1) == self.serial) {
+			true
+		} else {
+			lua.pop();
+			false
+		}
+	}
+
+	/// Returns whether the entity still exists and hasn't been replaced by another entity with the same index.
+	pub unsafe fn is_valid(&self, lua: State) -> bool {
+		let valid = self.resolve(lua);
+		if valid {
+			lua.pop();
+		}
+		valid
+	}
+
+	/// `Entity:GetClass()`, or `None` if the entity is no longer valid.
+	pub unsafe fn class(&self, lua: State) -> Option<String> {
+		self.call_method(lua, "GetClass", ()).ok()
+	}
+
+	/// Calls a method on the entity, like `ent:GetPos()` or `ent:SetColor(color)` in Lua.
+	///
+	/// # Example
+	///
+	/// ```ignore
+	/// let pos: gmod::userdata::Vector = ent.call_method(lua, "GetPos", ())?;
+	/// ent.call_method::<_, ()>(lua, "SetHealth", (100,))?;
+	/// ```
+	pub unsafe fn call_method<Args: PushToLuaMulti, Ret: FromLuaMulti>(&self, lua: State, method: &str, args: Args) -> Result<Ret, EntityError> {
+		if !self.resolve(lua) {
+			return Err(EntityError::Invalid);
+		}
+		let ret = call_method(lua, -1, method, args);
+		lua.pop();
+		ret
+	}
+}
+
+impl Default for Entity {
+	#[inline]
+	fn default() -> Self {
+		Entity::NULL
+	}
+}
+
+impl PushToLua for Entity {
+	/// Pushes the entity, or `NULL` if it's no longer valid.
+	unsafe fn push_to_lua(self, lua: State) {
+		if !self.resolve(lua) {
+			lua.get_global(crate::lua_string!("NULL"));
+		}
+	}
+}
+
+impl FromLua for Entity {
+	/// Reads an entity. `NULL` entities are read as `Entity::NULL`.
+	unsafe fn from_lua(lua: State, index: i32) -> Result<Self, FromLuaError> {
+		match lua::read_tagged_userdata(lua, index) {
+			Some(tagged) if tagged.resolved_type() == UserData::Entity => {},
+			_ => return Err(FromLuaError::type_mismatch(lua, index, "Entity")),
+		}
+
+		if !entity_exists(lua, index) {
+			return Ok(Entity::NULL);
+		}
+
+		match call_method::<_, i32>(lua, index, "EntIndex", ()) {
+			Ok(ent_index) => Ok(Entity {
+				index: ent_index,
+				serial: creation_id(lua, index),
+			}),
+			Err(_) => Err(FromLuaError::type_mismatch(lua, index, "Entity")),
+		}
+	}
+}
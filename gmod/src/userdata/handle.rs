@@ -0,0 +1,308 @@
+use std::ffi::c_void;
+
+use crate::lua::{FromLua, FromLuaError, LuaType, PushToLua, State};
+
+/// Bits of a handle used for the slot index.
+const INDEX_BITS: u32 = if usize::BITS >= 64 { 20 } else { 16 };
+
+/// Bits of a handle used for the generation.
+///
+/// On 64-bit, LuaJIT only keeps the low 47 bits of a light userdata, and GC64 builds split off the bits above 39 into a small table of "segments" which raises an error once it's full. Keeping handles below 2<sup>39</sup> means they all share the first segment.
+const GENERATION_BITS: u32 = if usize::BITS >= 64 { 19 } else { 16 };
+
+const INDEX_MASK: usize = (1 << INDEX_BITS) - 1;
+const GENERATION_MASK: usize = (1 << GENERATION_BITS) - 1;
+
+/// A reference to a value in a `HandleMap`, which Lua sees as a light userdata.
+///
+/// Handles are just numbers, so they're cheap to push and can't dangle: once the value is removed from the map, the handle is stale and resolving it fails. They don't record which map they came from, so only resolve a handle with the map that created it.
+///
+/// A slot's generation wraps around after 2<sup>19</sup> (2<sup>16</sup> on 32-bit) removals, so a stale handle could in theory be mistaken for a newer value if it's kept while its slot is reused that many times.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Handle {
+	index: usize,
+
+	/// Never zero, so a handle is never a NULL pointer
+	generation: usize,
+}
+impl Handle {
+	/// The light userdata pointer that represents this handle in Lua.
+	#[inline]
+	pub fn to_ptr(self) -> *mut c_void {
+		((self.generation << INDEX_BITS) | self.index) as *mut c_void
+	}
+
+	/// Reads a handle back from its light userdata pointer. Returns `None` for `NULL` and anything else that can't be a handle.
+	#[inline]
+	pub fn from_ptr(ptr: *mut c_void) -> Option<Handle> {
+		let bits = ptr as usize;
+		let generation = bits >> INDEX_BITS;
+		if generation == 0 || generation > GENERATION_MASK {
+			None
+		} else {
+			Some(Handle { index: bits & INDEX_MASK, generation })
+		}
+	}
+}
+impl PushToLua for Handle {
+	#[inline]
+	unsafe fn push_to_lua(self, lua: State) {
+		lua.push_lightuserdata(self.to_ptr());
+	}
+}
+impl FromLua for Handle {
+	unsafe fn from_lua(lua: State, index: i32) -> Result<Self, FromLuaError> {
+		if lua.type_of(index) == LuaType::LightUserData {
+			if let Some(handle) = Handle::from_ptr(lua.to_userdata(index)) {
+				return Ok(handle);
+			}
+		}
+		Err(FromLuaError::type_mismatch(lua, index, "handle"))
+	}
+}
+
+/// Why a `Handle` couldn't be resolved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HandleError {
+	/// The handle doesn't refer to any slot of this map
+	Invalid,
+
+	/// The value has been removed from the map
+	Stale,
+}
+impl std::fmt::Display for HandleError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			HandleError::Invalid => f.write_str("invalid handle"),
+			HandleError::Stale => f.write_str("stale handle"),
+		}
+	}
+}
+impl std::error::Error for HandleError {}
+
+struct Slot<T> {
+	generation: usize,
+	value: Option<T>,
+}
+impl<T> Slot<T> {
+	/// Invalidates handles to this slot. Generations wrap around, skipping zero so that handles are never NULL.
+	fn bump(&mut self) {
+		self.generation = (self.generation + 1) & GENERATION_MASK;
+		if self.generation == 0 {
+			self.generation = 1;
+		}
+	}
+}
+
+/// A slot map of Rust values which Lua can refer to with `Handle`s.
+///
+/// This is a cheap way of exposing native objects to Lua without allocating a userdata for each one every time they're pushed. Every slot has a generation which is bumped when its value is removed, so handles to removed values are detected rather than resolving to whatever took their place.
+///
+/// ```ignore
+/// thread_local! {
+///    static NAVMESHES: RefCell<HandleMap<NavMesh>> = RefCell::new(HandleMap::new());
+/// }
+///
+/// #[lua_function]
+/// unsafe fn load_navmesh(lua: gmod::lua::State) -> i32 {
+///    let handle = NAVMESHES.with(|navmeshes| navmeshes.borrow_mut().insert(NavMesh::load()));
+///    handle.push_to_lua(lua);
+///    1
+/// }
+///
+/// #[lua_function]
+/// unsafe fn navmesh_node_count(lua: gmod::lua::State) -> i32 {
+///    let count = NAVMESHES.with(|navmeshes| navmeshes.borrow().check(lua, 1).node_count());
+///    lua.push_integer(count as _);
+///    1
+/// }
+/// ```
+pub struct HandleMap<T> {
+	slots: Vec<Slot<T>>,
+
+	/// Indices of empty slots
+	free: Vec<usize>,
+}
+impl<T> Default for HandleMap<T> {
+	#[inline]
+	fn default() -> Self {
+		HandleMap::new()
+	}
+}
+impl<T> HandleMap<T> {
+	#[inline]
+	pub const fn new() -> Self {
+		HandleMap { slots: Vec::new(), free: Vec::new() }
+	}
+
+	/// Moves `value` into the map and returns its handle.
+	///
+	/// # Panics
+	///
+	/// Panics if the map already holds as many values as a handle can address (2<sup>20</sup>, or 2<sup>16</sup> on 32-bit).
+	pub fn insert(&mut self, value: T) -> Handle {
+		match self.free.pop() {
+			Some(index) => {
+				let slot = &mut self.slots[index];
+				slot.value = Some(value);
+				Handle { index, generation: slot.generation }
+			},
+			None => {
+				let index = self.slots.len();
+				assert!(index <= INDEX_MASK, "too many values in HandleMap");
+				self.slots.push(Slot { generation: 1, value: Some(value) });
+				Handle { index, generation: 1 }
+			}
+		}
+	}
+
+	fn slot(&self, handle: Handle) -> Result<&Slot<T>, HandleError> {
+		let slot = self.slots.get(handle.index).ok_or(HandleError::Invalid)?;
+		if slot.generation != handle.generation || slot.value.is_none() {
+			return Err(HandleError::Stale);
+		}
+		Ok(slot)
+	}
+
+	/// Returns the value `handle` refers to, unless it's been removed.
+	#[inline]
+	pub fn get(&self, handle: Handle) -> Result<&T, HandleError> {
+		self.slot(handle).map(|slot| slot.value.as_ref().unwrap())
+	}
+
+	/// Returns the value `handle` refers to, unless it's been removed.
+	#[inline]
+	pub fn get_mut(&mut self, handle: Handle) -> Result<&mut T, HandleError> {
+		self.slot(handle)?;
+		Ok(self.slots[handle.index].value.as_mut().unwrap())
+	}
+
+	#[inline]
+	pub fn contains(&self, handle: Handle) -> bool {
+		self.slot(handle).is_ok()
+	}
+
+	/// Removes the value `handle` refers to from the map, making every copy of the handle stale.
+	pub fn remove(&mut self, handle: Handle) -> Result<T, HandleError> {
+		self.slot(handle)?;
+		let slot = &mut self.slots[handle.index];
+		let value = slot.value.take().unwrap();
+		slot.bump();
+		self.free.push(handle.index);
+		Ok(value)
+	}
+
+	/// Removes every value from the map, making every handle stale.
+	pub fn clear(&mut self) {
+		for (index, slot) in self.slots.iter_mut().enumerate() {
+			if slot.value.take().is_some() {
+				slot.bump();
+				self.free.push(index);
+			}
+		}
+	}
+
+	#[inline]
+	pub fn len(&self) -> usize {
+		self.slots.len() - self.free.len()
+	}
+
+	#[inline]
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	/// Returns the value referred to by the handle at argument `arg`, raising a "bad argument" Lua error if it isn't a handle or it's stale.
+	pub unsafe fn check(&self, lua: State, arg: i32) -> &T {
+		match self.get(lua.check::<Handle>(arg)) {
+			Ok(value) => value,
+			Err(err) => lua.arg_error(arg, err.to_string()),
+		}
+	}
+
+	/// See `check`
+	pub unsafe fn check_mut(&mut self, lua: State, arg: i32) -> &mut T {
+		match self.get_mut(lua.check::<Handle>(arg)) {
+			Ok(value) => value,
+			Err(err) => lua.arg_error(arg, err.to_string()),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn insert_get_remove() {
+		let mut map = HandleMap::new();
+		let a = map.insert("a");
+		let b = map.insert("b");
+		assert_eq!(map.len(), 2);
+		assert_eq!(map.get(a), Ok(&"a"));
+		assert_eq!(map.get(b), Ok(&"b"));
+
+		*map.get_mut(b).unwrap() = "B";
+		assert_eq!(map.remove(b), Ok("B"));
+		assert_eq!(map.get(b), Err(HandleError::Stale));
+		assert_eq!(map.remove(b), Err(HandleError::Stale));
+		assert!(!map.contains(b));
+		assert_eq!(map.len(), 1);
+
+		let invalid = Handle { index: 5, generation: 1 };
+		assert_eq!(map.get(invalid), Err(HandleError::Invalid));
+	}
+
+	#[test]
+	fn reused_slots_get_new_generations() {
+		let mut map = HandleMap::new();
+		let old = map.insert(1);
+		map.remove(old).unwrap();
+		let new = map.insert(2);
+		assert_eq!(new.index, old.index);
+		assert_ne!(new, old);
+		assert_eq!(map.get(old), Err(HandleError::Stale));
+		assert_eq!(map.get(new), Ok(&2));
+	}
+
+	#[test]
+	fn clear() {
+		let mut map = HandleMap::new();
+		let handles = (0..3).map(|i| map.insert(i)).collect::<Vec<_>>();
+		map.clear();
+		assert!(map.is_empty());
+		assert!(handles.iter().all(|&handle| map.get(handle) == Err(HandleError::Stale)));
+
+		let handle = map.insert(3);
+		assert_eq!(map.get(handle), Ok(&3));
+		assert_eq!(map.len(), 1);
+	}
+
+	#[test]
+	fn generation_wraps_around_zero() {
+		let mut slot = Slot { generation: GENERATION_MASK, value: Some(()) };
+		slot.bump();
+		assert_eq!(slot.generation, 1);
+	}
+
+	#[test]
+	fn pointers() {
+		let mut map = HandleMap::new();
+		let mut handle = map.insert(());
+		for _ in 0..3 {
+			map.remove(handle).unwrap();
+			handle = map.insert(());
+		}
+		assert_eq!(Handle::from_ptr(handle.to_ptr()), Some(handle));
+
+		let last = Handle { index: INDEX_MASK, generation: GENERATION_MASK };
+		assert_eq!(Handle::from_ptr(last.to_ptr()), Some(last));
+		if usize::BITS >= 64 {
+			assert!((last.to_ptr() as usize) < 1 << 39);
+		}
+
+		assert_eq!(Handle::from_ptr(std::ptr::null_mut()), None);
+		assert_eq!(Handle::from_ptr(INDEX_MASK as *mut c_void), None);
+		assert_eq!(Handle::from_ptr(((GENERATION_MASK + 1) << INDEX_BITS) as *mut c_void), None);
+	}
+}
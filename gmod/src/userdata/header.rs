@@ -0,0 +1,392 @@
+use std::{any::TypeId, cell::Cell, ffi::c_void, ptr, sync::atomic::{AtomicU64, Ordering}};
+
+use crate::lua::{self, LuaType, State, LUA_REGISTRYINDEX, LUA_SHARED};
+
+use super::{BorrowError, BorrowFlag, BorrowState, UserDataRef, UserDataRefMut};
+
+/// Identifies userdata created by gmod-rs. Bump the version byte whenever the header layout changes.
+const MAGIC: [u8; 8] = *b"gmodrs\x04\x00";
+
+/// The address of this static is unique to each loaded binary module, so it identifies which module created a userdata.
+static MODULE: u8 = 0;
+
+#[inline]
+/// A pointer unique to this binary module.
+pub(crate) fn module() -> *const u8 {
+	&MODULE
+}
+
+static NEXT_GENERATION: AtomicU64 = AtomicU64::new(1);
+
+/// Marks the registry table keeping userdata that were garbage collected while borrowed alive until they're released. Only its address is used.
+static PENDING_DROPS_KEY: u8 = 0;
+
+thread_local! {
+	/// The most recently created Rust userdata whose value still needs dropping. The rest are linked through their headers.
+	static LIVE: Cell<*mut RustUserDataHeader> = const { Cell::new(ptr::null_mut()) };
+}
+
+/// Prepended to every userdata created by `new_userdata` and `push_userdata`.
+#[repr(C)]
+pub(crate) struct RustUserDataHeader {
+	magic: [u8; 8],
+	module: *const u8,
+	type_id: TypeId,
+
+	/// Unique (per module) for every userdata, and set to zero once the value has been dropped by `__gc`.
+	generation: u64,
+
+	/// See `BorrowFlag`
+	borrow: BorrowState,
+
+	/// Set if the userdata was garbage collected while borrowed, so that the value is dropped once the last borrow is released instead
+	drop_pending: bool,
+
+	/// Drops the value
+	drop: unsafe fn(*mut RustUserDataHeader),
+
+	/// Whether this is in the `LIVE` list, which values that need dropping are until they're dropped
+	linked: bool,
+	prev: *mut RustUserDataHeader,
+	next: *mut RustUserDataHeader,
+}
+
+/// Why a value couldn't be downcast to a Rust userdata type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RustUserDataError {
+	/// The value isn't a full userdata
+	NotUserData(LuaType),
+
+	/// The userdata wasn't created by gmod-rs
+	NotRustUserData,
+
+	/// The userdata was created by another gmod-rs module loaded in this process
+	ForeignModule,
+
+	/// The userdata holds a different Rust type
+	WrongType,
+
+	/// The userdata has already been garbage collected and its value dropped
+	Finalized,
+
+	/// The value is already borrowed
+	Borrow(BorrowError),
+}
+impl std::fmt::Display for RustUserDataError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			RustUserDataError::NotUserData(lua_type) => write!(f, "got {}", lua_type),
+			RustUserDataError::NotRustUserData => f.write_str("got foreign userdata"),
+			RustUserDataError::ForeignModule => f.write_str("got userdata from another module"),
+			RustUserDataError::WrongType => f.write_str("got userdata of another type"),
+			RustUserDataError::Finalized => f.write_str("got garbage collected userdata"),
+			RustUserDataError::Borrow(err) => err.fmt(f),
+		}
+	}
+}
+impl std::error::Error for RustUserDataError {}
+impl From<BorrowError> for RustUserDataError {
+	#[inline]
+	fn from(err: BorrowError) -> Self {
+		RustUserDataError::Borrow(err)
+	}
+}
+
+/// The message of the "bad argument" error raised when argument `arg` isn't a usable `name`.
+pub(crate) fn arg_error_message(name: &str, err: RustUserDataError) -> String {
+	match err {
+		RustUserDataError::Borrow(err) => format!("{} is {}", name, err),
+		err => format!("{} expected, {}", name, err),
+	}
+}
+
+#[inline(always)]
+/// Offset of the value from the start of the userdata block.
+pub(crate) const fn data_offset<T>() -> usize {
+	let size = std::mem::size_of::<RustUserDataHeader>();
+	let align = std::mem::align_of::<T>();
+	size.div_ceil(align) * align
+}
+
+/// The alignment Lua guarantees for userdata blocks, which is what its own types need.
+const MAX_ALIGN: usize = 8;
+
+/// Fails to compile for types that Lua can't align, when `alloc` is instantiated with them.
+struct AssertAligned<T>(std::marker::PhantomData<T>);
+impl<T> AssertAligned<T> {
+	const OK: () = assert!(std::mem::align_of::<T>() <= MAX_ALIGN, "Lua userdata can't be aligned for this type, use new_boxed_userdata instead");
+}
+
+/// Allocates a new userdata holding `data` behind a `RustUserDataHeader` and pushes it onto the stack.
+pub(crate) unsafe fn alloc<T: 'static>(lua: State, data: T) -> *mut T {
+	// Lua only aligns userdata for its own types, so over-aligned values have to be boxed
+	#[allow(clippy::let_unit_value)]
+	let () = AssertAligned::<T>::OK;
+
+	let block = (LUA_SHARED.lua_newuserdata)(lua, data_offset::<T>() + std::mem::size_of::<T>());
+	let ptr = (block as *mut u8).add(data_offset::<T>()) as *mut T;
+
+	(block as *mut RustUserDataHeader).write(RustUserDataHeader {
+		magic: MAGIC,
+		module: &MODULE,
+		type_id: TypeId::of::<T>(),
+		generation: NEXT_GENERATION.fetch_add(1, Ordering::Relaxed),
+		borrow: 0,
+		drop_pending: false,
+		drop: drop_value::<T>,
+		linked: false,
+		prev: ptr::null_mut(),
+		next: ptr::null_mut(),
+	});
+	ptr.write(data);
+
+	if std::mem::needs_drop::<T>() {
+		link(block as *mut RustUserDataHeader);
+	}
+
+	ptr
+}
+
+/// Adds a header to the front of the `LIVE` list.
+unsafe fn link(header: *mut RustUserDataHeader) {
+	LIVE.with(|live| {
+		let next = live.get();
+		if !next.is_null() {
+			(*next).prev = header;
+		}
+		(*header).next = next;
+		(*header).linked = true;
+		live.set(header);
+	});
+}
+
+/// Removes a header from the `LIVE` list, if it's in it.
+unsafe fn unlink(header: *mut RustUserDataHeader) {
+	if !(*header).linked {
+		return;
+	}
+	let (prev, next) = ((*header).prev, (*header).next);
+	if prev.is_null() {
+		LIVE.with(|live| live.set(next));
+	} else {
+		(*prev).next = next;
+	}
+	if !next.is_null() {
+		(*next).prev = prev;
+	}
+	(*header).linked = false;
+	(*header).prev = ptr::null_mut();
+	(*header).next = ptr::null_mut();
+}
+
+/// Validates the userdata at `index` and returns its header and a pointer to its value.
+pub(crate) unsafe fn header<T: 'static>(lua: State, index: i32) -> Result<(*mut RustUserDataHeader, *mut T), RustUserDataError> {
+	let lua_type = lua.type_of(index);
+	if lua_type != LuaType::UserData {
+		return Err(RustUserDataError::NotUserData(lua_type));
+	}
+
+	let block = lua.to_userdata(index);
+	if (lua.len(index) as usize) < std::mem::size_of::<RustUserDataHeader>() {
+		return Err(RustUserDataError::NotRustUserData);
+	}
+
+	let header = block as *mut RustUserDataHeader;
+	if (*header).magic != MAGIC {
+		return Err(RustUserDataError::NotRustUserData);
+	}
+	if !std::ptr::eq((*header).module, &MODULE) {
+		return Err(RustUserDataError::ForeignModule);
+	}
+	if (*header).type_id != TypeId::of::<T>() {
+		return Err(RustUserDataError::WrongType);
+	}
+	if (*header).generation == 0 {
+		return Err(RustUserDataError::Finalized);
+	}
+
+	Ok((header, (block as *mut u8).add(data_offset::<T>()) as *mut T))
+}
+
+/// The borrow state of the userdata behind `header`.
+#[inline]
+pub(crate) unsafe fn borrow_state(header: *mut RustUserDataHeader) -> BorrowState {
+	(*header).borrow
+}
+
+/// Sets the borrow state of the userdata behind `header`, dropping its value if it was garbage collected while borrowed and this releases the last borrow.
+pub(crate) unsafe fn set_borrow_state(header: *mut RustUserDataHeader, state: BorrowState) {
+	(*header).borrow = state;
+	if state == 0 && (*header).drop_pending {
+		(*header).drop_pending = false;
+		drop_now(header);
+
+		// The userdata is no longer needed, let the garbage collector free it
+		let lua = lua::state();
+		push_pending_drops(lua);
+		lua.push_lightuserdata(header as *mut c_void);
+		lua.push_nil();
+		lua.raw_set(-3);
+		lua.pop();
+	}
+}
+
+/// Takes a shared borrow of the Rust userdata at `index`.
+pub(crate) unsafe fn borrow<T: 'static>(lua: &State, index: i32) -> Result<UserDataRef<'_, T>, RustUserDataError> {
+	let (header, ptr) = header::<T>(*lua, index)?;
+	Ok(UserDataRef::new(ptr, BorrowFlag::Header(header))?)
+}
+
+/// Takes a mutable borrow of the Rust userdata at `index`.
+pub(crate) unsafe fn borrow_mut<T: 'static>(lua: &State, index: i32) -> Result<UserDataRefMut<'_, T>, RustUserDataError> {
+	let (header, ptr) = header::<T>(*lua, index)?;
+	Ok(UserDataRefMut::new(ptr, BorrowFlag::Header(header))?)
+}
+
+/// Takes a shared borrow of the value boxed in the Rust userdata at `index`, sharing the borrow state of the box.
+pub(crate) unsafe fn borrow_boxed<T: ?Sized + 'static>(lua: &State, index: i32) -> Result<UserDataRef<'_, T>, RustUserDataError> {
+	let (header, ptr) = header::<Box<T>>(*lua, index)?;
+	Ok(UserDataRef::new(&**ptr as *const T, BorrowFlag::Header(header))?)
+}
+
+/// Takes a mutable borrow of the value boxed in the Rust userdata at `index`.
+pub(crate) unsafe fn borrow_boxed_mut<T: ?Sized + 'static>(lua: &State, index: i32) -> Result<UserDataRefMut<'_, T>, RustUserDataError> {
+	let (header, ptr) = header::<Box<T>>(*lua, index)?;
+	Ok(UserDataRefMut::new(&mut **ptr as *mut T, BorrowFlag::Header(header))?)
+}
+
+/// The `drop` of a `RustUserDataHeader` holding a `T`.
+unsafe fn drop_value<T>(header: *mut RustUserDataHeader) {
+	std::ptr::drop_in_place((header as *mut u8).add(data_offset::<T>()) as *mut T);
+}
+
+/// Drops the value behind `header`, marking it as finalized.
+unsafe fn drop_now(header: *mut RustUserDataHeader) {
+	unlink(header);
+	(*header).generation = 0;
+	((*header).drop)(header);
+}
+
+/// Pushes the table of userdata with a pending drop, creating it first if needed.
+unsafe fn push_pending_drops(lua: State) {
+	lua.push_lightuserdata(&PENDING_DROPS_KEY as *const u8 as *mut c_void);
+	lua.raw_get(LUA_REGISTRYINDEX);
+	if lua.is_table(-1) {
+		return;
+	}
+	lua.pop();
+
+	lua.new_table();
+	lua.push_lightuserdata(&PENDING_DROPS_KEY as *const u8 as *mut c_void);
+	lua.push_value(-2);
+	lua.raw_set(LUA_REGISTRYINDEX);
+}
+
+/// Drops the value of a Rust userdata, marking it as finalized. Does nothing if it's already been dropped or isn't a `T`.
+///
+/// If the value is still borrowed (for example by a guard whose userdata was popped off the stack), the userdata is resurrected by referencing it from the registry, and its value is dropped when the last borrow is released.
+pub(crate) unsafe fn finalize<T: 'static>(lua: State, index: i32) {
+	let header = match header::<T>(lua, index) {
+		Ok((header, _)) => header,
+		Err(_) => return,
+	};
+
+	if (*header).borrow == 0 {
+		drop_now(header);
+		return;
+	}
+
+	(*header).drop_pending = true;
+	let index = lua.abs_index(index);
+	push_pending_drops(lua);
+	lua.push_lightuserdata(header as *mut c_void);
+	lua.push_value(index);
+	lua.raw_set(-3);
+	lua.pop();
+}
+
+/// Drops the value of every Rust userdata that hasn't been dropped yet, including any that are still borrowed, as their `__gc` metamethods won't be callable once the module is unloaded.
+pub(crate) unsafe fn finalize_all(lua: State) {
+	loop {
+		let header = LIVE.with(Cell::get);
+		if header.is_null() {
+			break;
+		}
+		(*header).drop_pending = false;
+		drop_now(header);
+	}
+
+	// Let the garbage collector free the userdata that were waiting for a borrow to be released
+	lua.push_lightuserdata(&PENDING_DROPS_KEY as *const u8 as *mut c_void);
+	lua.push_nil();
+	lua.raw_set(LUA_REGISTRYINDEX);
+}
+
+impl State {
+	/// Borrows the Rust value of type `T` stored in the userdata at the given index.
+	///
+	/// This works for any userdata created by `new_userdata` or `push_userdata`, and checks that it holds a `T`, was created by this module (not another gmod-rs module loaded in the same process), and hasn't been garbage collected yet.
+	///
+	/// Like `RefCell::try_borrow`, this fails if the value is currently mutably borrowed. If the userdata is garbage collected while the borrow is held, dropping the value is put off until it's released.
+	pub unsafe fn try_rust_userdata<T: 'static>(&self, index: i32) -> Result<UserDataRef<'_, T>, RustUserDataError> {
+		borrow(self, index)
+	}
+
+	/// Mutably borrows the Rust value of type `T` stored in the userdata at the given index.
+	///
+	/// See `try_rust_userdata`. Like `RefCell::try_borrow_mut`, this fails if the value is currently borrowed at all.
+	pub unsafe fn try_rust_userdata_mut<T: 'static>(&self, index: i32) -> Result<UserDataRefMut<'_, T>, RustUserDataError> {
+		borrow_mut(self, index)
+	}
+
+	/// Like `try_rust_userdata`, but raises a "bad argument" Lua error if argument `arg` isn't a `T` or can't be borrowed.
+	pub unsafe fn check_rust_userdata<T: 'static>(&self, arg: i32) -> UserDataRef<'_, T> {
+		match borrow(self, arg) {
+			Ok(borrow) => borrow,
+			Err(err) => self.arg_error(arg, arg_error_message(std::any::type_name::<T>(), err)),
+		}
+	}
+
+	/// See `check_rust_userdata`
+	pub unsafe fn check_rust_userdata_mut<T: 'static>(&self, arg: i32) -> UserDataRefMut<'_, T> {
+		match borrow_mut(self, arg) {
+			Ok(borrow) => borrow,
+			Err(err) => self.arg_error(arg, arg_error_message(std::any::type_name::<T>(), err)),
+		}
+	}
+
+	/// Borrows the value boxed in the userdata at the given index by `new_boxed_userdata`.
+	///
+	/// `T` must be exactly the type the box was created with, e.g. `dyn Any` for a `Box<dyn Any>`. See `try_rust_userdata`.
+	pub unsafe fn try_boxed_userdata<T: ?Sized + 'static>(&self, index: i32) -> Result<UserDataRef<'_, T>, RustUserDataError> {
+		borrow_boxed(self, index)
+	}
+
+	/// Mutably borrows the value boxed in the userdata at the given index by `new_boxed_userdata`. See `try_boxed_userdata`.
+	pub unsafe fn try_boxed_userdata_mut<T: ?Sized + 'static>(&self, index: i32) -> Result<UserDataRefMut<'_, T>, RustUserDataError> {
+		borrow_boxed_mut(self, index)
+	}
+
+	/// Like `try_boxed_userdata`, but raises a "bad argument" Lua error if argument `arg` isn't a boxed `T` or can't be borrowed.
+	pub unsafe fn check_boxed_userdata<T: ?Sized + 'static>(&self, arg: i32) -> UserDataRef<'_, T> {
+		match borrow_boxed(self, arg) {
+			Ok(borrow) => borrow,
+			Err(err) => self.arg_error(arg, arg_error_message(std::any::type_name::<T>(), err)),
+		}
+	}
+
+	/// See `check_boxed_userdata`
+	pub unsafe fn check_boxed_userdata_mut<T: ?Sized + 'static>(&self, arg: i32) -> UserDataRefMut<'_, T> {
+		match borrow_boxed_mut(self, arg) {
+			Ok(borrow) => borrow,
+			Err(err) => self.arg_error(arg, arg_error_message(std::any::type_name::<T>(), err)),
+		}
+	}
+
+	/// Returns the generation of the Rust userdata at the given index.
+	///
+	/// Every userdata gets a new generation when it's created, so this can be stored alongside a pointer to tell whether it still refers to the same object.
+	pub unsafe fn rust_userdata_generation<T: 'static>(&self, index: i32) -> Result<u64, RustUserDataError> {
+		header::<T>(*self, index).map(|(header, _)| (*header).generation)
+	}
+}
@@ -0,0 +1,430 @@
+use std::ops::{Add, AddAssign, Index, IndexMut, Mul, MulAssign, Sub, SubAssign};
+
+use super::{Angle, Vector};
+
+/// The engine's `VMatrix`: a 4x4 row-major matrix, where the translation is in the last column.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[repr(C)]
+pub struct Matrix {
+	pub m: [[f32; 4]; 4]
+}
+
+impl Default for Matrix {
+	#[inline]
+	fn default() -> Self {
+		Matrix::IDENTITY
+	}
+}
+
+impl Matrix {
+	pub const IDENTITY: Matrix = Matrix::new([
+		[1.0, 0.0, 0.0, 0.0],
+		[0.0, 1.0, 0.0, 0.0],
+		[0.0, 0.0, 1.0, 0.0],
+		[0.0, 0.0, 0.0, 1.0],
+	]);
+
+	pub const ZERO: Matrix = Matrix::new([[0.0; 4]; 4]);
+
+	#[inline]
+	pub const fn new(m: [[f32; 4]; 4]) -> Matrix {
+		Matrix { m }
+	}
+
+	#[inline]
+	/// A translation matrix.
+	pub fn from_translation(translation: Vector) -> Matrix {
+		let mut matrix = Matrix::IDENTITY;
+		matrix.set_translation(translation);
+		matrix
+	}
+
+	#[inline]
+	/// A rotation matrix.
+	pub fn from_angles(angles: Angle) -> Matrix {
+		let mut matrix = Matrix::IDENTITY;
+		matrix.set_angles(angles);
+		matrix
+	}
+
+	#[inline]
+	/// A scaling matrix.
+	pub fn from_scale(scale: Vector) -> Matrix {
+		Matrix::new([
+			[scale.x, 0.0, 0.0, 0.0],
+			[0.0, scale.y, 0.0, 0.0],
+			[0.0, 0.0, scale.z, 0.0],
+			[0.0, 0.0, 0.0, 1.0],
+		])
+	}
+
+	#[inline]
+	fn column(&self, column: usize) -> Vector {
+		Vector::new(self.m[0][column], self.m[1][column], self.m[2][column])
+	}
+
+	#[inline]
+	fn set_column(&mut self, column: usize, v: Vector) {
+		self.m[0][column] = v.x;
+		self.m[1][column] = v.y;
+		self.m[2][column] = v.z;
+	}
+
+	#[inline]
+	/// `VMatrix:GetForward()`
+	pub fn forward(&self) -> Vector {
+		self.column(0)
+	}
+
+	#[inline]
+	/// `VMatrix:GetRight()`
+	pub fn right(&self) -> Vector {
+		-self.column(1)
+	}
+
+	#[inline]
+	/// `VMatrix:GetUp()`
+	pub fn up(&self) -> Vector {
+		self.column(2)
+	}
+
+	#[inline]
+	/// `VMatrix:GetTranslation()`
+	pub fn translation(&self) -> Vector {
+		self.column(3)
+	}
+
+	#[inline]
+	/// `VMatrix:SetTranslation(translation)`
+	pub fn set_translation(&mut self, translation: Vector) {
+		self.set_column(3, translation);
+	}
+
+	/// `VMatrix:GetAngles()`
+	///
+	/// Extracts the rotation like the engine's `MatrixAngles`. The result is only meaningful if the matrix has no scale or shear.
+	pub fn angles(&self) -> Angle {
+		const RAD2DEG: f32 = 180.0 / std::f32::consts::PI;
+
+		let forward = self.column(0);
+		let left = self.column(1);
+		let up_z = self.m[2][2];
+
+		let xy_dist = forward.length_2d();
+		if xy_dist > 0.001 {
+			Angle::new(
+				(-forward.z).atan2(xy_dist) * RAD2DEG,
+				forward.y.atan2(forward.x) * RAD2DEG,
+				left.z.atan2(up_z) * RAD2DEG,
+			)
+		} else {
+			Angle::new(
+				(-forward.z).atan2(xy_dist) * RAD2DEG,
+				(-left.x).atan2(left.y) * RAD2DEG,
+				0.0,
+			)
+		}
+	}
+
+	/// `VMatrix:SetAngles(angles)`
+	///
+	/// Replaces the rotation (and any scale) of the matrix, keeping its translation.
+	pub fn set_angles(&mut self, angles: Angle) {
+		let [x, y, z] = angles.matrix();
+		self.m[0][..3].copy_from_slice(&[x.x, x.y, x.z]);
+		self.m[1][..3].copy_from_slice(&[y.x, y.y, y.z]);
+		self.m[2][..3].copy_from_slice(&[z.x, z.y, z.z]);
+	}
+
+	#[inline]
+	/// The length of each basis vector.
+	pub fn scale(&self) -> Vector {
+		Vector::new(self.column(0).length(), self.column(1).length(), self.column(2).length())
+	}
+
+	/// Rescales each basis vector to the given length, keeping the rotation and translation.
+	pub fn set_scale(&mut self, scale: Vector) {
+		let current = self.scale();
+		for (column, (current, scale)) in [(current.x, scale.x), (current.y, scale.y), (current.z, scale.z)].into_iter().enumerate() {
+			let v = if current == 0.0 { Vector::ZERO } else { self.column(column) * (scale / current) };
+			self.set_column(column, v);
+		}
+	}
+
+	#[inline]
+	/// `VMatrix:Translate(translation)`
+	pub fn translate(&mut self, translation: Vector) {
+		*self *= Matrix::from_translation(translation);
+	}
+
+	#[inline]
+	/// `VMatrix:Rotate(angles)`
+	pub fn rotate(&mut self, angles: Angle) {
+		*self *= Matrix::from_angles(angles);
+	}
+
+	#[inline]
+	/// `VMatrix:Scale(scale)`
+	pub fn scale_by(&mut self, scale: Vector) {
+		*self *= Matrix::from_scale(scale);
+	}
+
+	#[inline]
+	#[must_use]
+	pub fn transposed(&self) -> Matrix {
+		let mut out = Matrix::ZERO;
+		for i in 0..4 {
+			for j in 0..4 {
+				out.m[i][j] = self.m[j][i];
+			}
+		}
+		out
+	}
+
+	#[inline]
+	/// Transforms a point, including the translation (`VMatrix::VMul4x3`).
+	pub fn transform_point(&self, point: Vector) -> Vector {
+		self.transform_vector(point) + self.translation()
+	}
+
+	#[inline]
+	/// Transforms a direction, ignoring the translation (`VMatrix::VMul3x3`).
+	pub fn transform_vector(&self, v: Vector) -> Vector {
+		Vector::new(
+			self.m[0][0] * v.x + self.m[0][1] * v.y + self.m[0][2] * v.z,
+			self.m[1][0] * v.x + self.m[1][1] * v.y + self.m[1][2] * v.z,
+			self.m[2][0] * v.x + self.m[2][1] * v.y + self.m[2][2] * v.z,
+		)
+	}
+
+	/// `VMatrix:GetInverse()`
+	///
+	/// Returns `None` if the matrix isn't invertible.
+	pub fn inverse(&self) -> Option<Matrix> {
+		// Gauss-Jordan elimination with partial pivoting, like VMatrix::InverseGeneral
+		let mut m = self.m;
+		let mut out = Matrix::IDENTITY.m;
+
+		for column in 0..4 {
+			let pivot = (column..4).max_by(|&a, &b| m[a][column].abs().total_cmp(&m[b][column].abs()))?;
+			if m[pivot][column] == 0.0 {
+				return None;
+			}
+			m.swap(column, pivot);
+			out.swap(column, pivot);
+
+			let inv = 1.0 / m[column][column];
+			for j in 0..4 {
+				m[column][j] *= inv;
+				out[column][j] *= inv;
+			}
+
+			for row in 0..4 {
+				if row == column {
+					continue;
+				}
+				let factor = m[row][column];
+				if factor == 0.0 {
+					continue;
+				}
+				for j in 0..4 {
+					m[row][j] -= factor * m[column][j];
+					out[row][j] -= factor * out[column][j];
+				}
+			}
+		}
+
+		Some(Matrix::new(out))
+	}
+
+	/// `VMatrix:GetInverseTR()`
+	///
+	/// A much faster inverse for matrices that only contain a rotation and translation.
+	pub fn inverse_tr(&self) -> Matrix {
+		let mut out = Matrix::IDENTITY;
+		for i in 0..3 {
+			for j in 0..3 {
+				out.m[i][j] = self.m[j][i];
+			}
+		}
+		out.set_translation(-out.transform_vector(self.translation()));
+		out
+	}
+
+	#[inline]
+	/// `VMatrix:IsIdentity()`
+	pub fn is_identity(&self) -> bool {
+		*self == Matrix::IDENTITY
+	}
+}
+
+impl From<Angle> for Matrix {
+	#[inline]
+	fn from(angles: Angle) -> Self {
+		Matrix::from_angles(angles)
+	}
+}
+
+impl Index<usize> for Matrix {
+	type Output = [f32; 4];
+
+	#[inline]
+	fn index(&self, row: usize) -> &[f32; 4] {
+		&self.m[row]
+	}
+}
+impl IndexMut<usize> for Matrix {
+	#[inline]
+	fn index_mut(&mut self, row: usize) -> &mut [f32; 4] {
+		&mut self.m[row]
+	}
+}
+
+impl Add for Matrix {
+	type Output = Matrix;
+
+	fn add(mut self, rhs: Matrix) -> Matrix {
+		for (row, rhs) in self.m.iter_mut().zip(rhs.m) {
+			for (a, b) in row.iter_mut().zip(rhs) {
+				*a += b;
+			}
+		}
+		self
+	}
+}
+impl Sub for Matrix {
+	type Output = Matrix;
+
+	fn sub(mut self, rhs: Matrix) -> Matrix {
+		for (row, rhs) in self.m.iter_mut().zip(rhs.m) {
+			for (a, b) in row.iter_mut().zip(rhs) {
+				*a -= b;
+			}
+		}
+		self
+	}
+}
+impl Mul for Matrix {
+	type Output = Matrix;
+
+	fn mul(self, rhs: Matrix) -> Matrix {
+		let mut out = Matrix::ZERO;
+		for i in 0..4 {
+			for j in 0..4 {
+				out.m[i][j] = self.m[i][0] * rhs.m[0][j] + self.m[i][1] * rhs.m[1][j] + self.m[i][2] * rhs.m[2][j] + self.m[i][3] * rhs.m[3][j];
+			}
+		}
+		out
+	}
+}
+impl Mul<Vector> for Matrix {
+	type Output = Vector;
+
+	#[inline]
+	/// See `transform_point`
+	fn mul(self, rhs: Vector) -> Vector {
+		self.transform_point(rhs)
+	}
+}
+impl AddAssign for Matrix {
+	#[inline]
+	fn add_assign(&mut self, rhs: Matrix) {
+		*self = *self + rhs;
+	}
+}
+impl SubAssign for Matrix {
+	#[inline]
+	fn sub_assign(&mut self, rhs: Matrix) {
+		*self = *self - rhs;
+	}
+}
+impl MulAssign for Matrix {
+	#[inline]
+	fn mul_assign(&mut self, rhs: Matrix) {
+		*self = *self * rhs;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn assert_matrix_eq(a: Matrix, b: Matrix) {
+		for i in 0..4 {
+			for j in 0..4 {
+				assert!((a.m[i][j] - b.m[i][j]).abs() < 1e-5, "{:?} != {:?}", a, b);
+			}
+		}
+	}
+
+	fn trs() -> Matrix {
+		let mut matrix = Matrix::from_translation(Vector::new(10.0, -20.0, 30.0));
+		matrix.rotate(Angle::new(30.0, 45.0, 60.0));
+		matrix.scale_by(Vector::new(2.0, 3.0, 4.0));
+		matrix
+	}
+
+	#[test]
+	fn inverse() {
+		assert_eq!(Matrix::IDENTITY.inverse(), Some(Matrix::IDENTITY));
+		assert_eq!(Matrix::ZERO.inverse(), None);
+		assert_eq!(Matrix::from_scale(Vector::new(1.0, 0.0, 1.0)).inverse(), None);
+
+		let matrix = trs();
+		let inverse = matrix.inverse().unwrap();
+		assert_matrix_eq(matrix * inverse, Matrix::IDENTITY);
+		assert_matrix_eq(inverse * matrix, Matrix::IDENTITY);
+
+		let point = Vector::new(1.0, 2.0, 3.0);
+		assert!(inverse.transform_point(matrix.transform_point(point)).is_equal_tol(point, 1e-4));
+	}
+
+	#[test]
+	fn inverse_tr() {
+		let mut matrix = Matrix::from_translation(Vector::new(5.0, 6.0, 7.0));
+		matrix.rotate(Angle::new(-10.0, 100.0, 20.0));
+		assert_matrix_eq(matrix.inverse_tr(), matrix.inverse().unwrap());
+	}
+
+	#[test]
+	fn multiply() {
+		let a = Vector::new(1.0, 2.0, 3.0);
+		let b = Vector::new(-4.0, 5.0, 0.5);
+		assert_eq!(Matrix::from_translation(a) * Matrix::from_translation(b), Matrix::from_translation(a + b));
+		assert_eq!(Matrix::IDENTITY * trs(), trs());
+		assert_eq!(trs() * Matrix::IDENTITY, trs());
+
+		let yaw = Matrix::from_angles(Angle::new(0.0, 90.0, 0.0));
+		assert_matrix_eq(yaw * yaw, Matrix::from_angles(Angle::new(0.0, 180.0, 0.0)));
+
+		// Translate, then rotate, then scale a point, applied right to left like the engine's VMatrix
+		let point = Vector::new(1.0, 0.0, 0.0);
+		let moved = trs().transform_point(point);
+		let by_hand = Matrix::from_translation(Vector::new(10.0, -20.0, 30.0))
+			.transform_point(Matrix::from_angles(Angle::new(30.0, 45.0, 60.0)).transform_vector(Vector::new(2.0, 0.0, 0.0)));
+		assert!(moved.is_equal_tol(by_hand, 1e-4));
+		assert!((trs() * point).is_equal_tol(moved, 1e-4));
+	}
+
+	#[test]
+	fn angles_round_trip() {
+		for angles in [
+			Angle::new(0.0, 0.0, 0.0),
+			Angle::new(30.0, 45.0, 60.0),
+			Angle::new(-45.0, -135.0, 170.0),
+			Angle::new(89.0, 10.0, -20.0),
+		] {
+			let back = Matrix::from_angles(angles).angles();
+			assert!(back.is_equal_tol(angles, 1e-3), "{:?} came back as {:?}", angles, back);
+		}
+
+		// Looking straight up or down, the roll is folded into the yaw
+		let back = Matrix::from_angles(Angle::new(90.0, 30.0, 0.0)).angles();
+		assert!(back.is_equal_tol(Angle::new(90.0, 30.0, 0.0), 1e-3), "came back as {:?}", back);
+
+		let mut matrix = Matrix::from_translation(Vector::new(1.0, 2.0, 3.0));
+		matrix.set_angles(Angle::new(10.0, 20.0, 30.0));
+		assert!(matrix.angles().is_equal_tol(Angle::new(10.0, 20.0, 30.0), 1e-3));
+		assert_eq!(matrix.translation(), Vector::new(1.0, 2.0, 3.0));
+	}
+}
@@ -0,0 +1,169 @@
+//! Metamethods derived from Rust traits, for `LuaUserData` types.
+//!
+//! These are usually set with `#[lua_class(meta(...))]`, but can also be added by hand with `UserDataMethods::add_meta`:
+//!
+//! ```ignore
+//! methods.add_meta("__tostring", gmod::userdata::meta::tostring::<BigInt>);
+//! methods.add_meta("__mul", gmod::userdata::meta::mul_rhs::<Vec3, f64>);
+//! ```
+//!
+//! Arithmetic metamethods clone their operands and push the result with `push_userdata`, so `T` must be `Clone` and the operator's `Output` must be a `LuaUserData`.
+
+use std::{fmt::Display, ops::{Add, Div, Mul, Neg, Sub}};
+
+use crate::lua::{FromLua, State};
+
+use super::LuaUserData;
+
+/// The userdata equivalent of `ExactSizeIterator::len`, used for `__len` (the `#` operator).
+pub trait LuaLen {
+	fn lua_len(&self) -> usize;
+}
+
+/// `__tostring` from `Display`
+pub unsafe extern "C-unwind" fn tostring<T: LuaUserData + Display>(lua: State) -> i32 {
+	let this = lua.check_class::<T>(1);
+	lua.push_string(&this.to_string());
+	1
+}
+
+/// `__eq` from `PartialEq`
+///
+/// Lua only calls `__eq` when both operands are userdata sharing the same metamethod, but they're still checked to be `T`.
+pub unsafe extern "C-unwind" fn eq<T: LuaUserData + PartialEq>(lua: State) -> i32 {
+	let eq = match (lua.test_class::<T>(1), lua.test_class::<T>(2)) {
+		(Some(a), Some(b)) => *a == *b,
+		_ => false,
+	};
+	lua.push_boolean(eq);
+	1
+}
+
+/// `__lt` from `PartialOrd`
+pub unsafe extern "C-unwind" fn lt<T: LuaUserData + PartialOrd>(lua: State) -> i32 {
+	let lt = *lua.check_class::<T>(1) < *lua.check_class::<T>(2);
+	lua.push_boolean(lt);
+	1
+}
+
+/// `__le` from `PartialOrd`
+pub unsafe extern "C-unwind" fn le<T: LuaUserData + PartialOrd>(lua: State) -> i32 {
+	let le = *lua.check_class::<T>(1) <= *lua.check_class::<T>(2);
+	lua.push_boolean(le);
+	1
+}
+
+/// `__unm` from `Neg`
+pub unsafe extern "C-unwind" fn unm<T>(lua: State) -> i32
+where
+	T: LuaUserData + Clone + Neg,
+	T::Output: LuaUserData,
+{
+	let result = -T::clone(&lua.check_class::<T>(1));
+	lua.push_userdata(result);
+	1
+}
+
+/// `__len` from `LuaLen`
+pub unsafe extern "C-unwind" fn len<T: LuaUserData + LuaLen>(lua: State) -> i32 {
+	let len = lua.check_class::<T>(1).lua_len();
+	lua.push_integer(len as _);
+	1
+}
+
+#[doc(hidden)]
+/// Raises the error for an arithmetic metamethod shared by several `meta(...)` traits when none of them take the operands.
+pub unsafe fn __no_overload(lua: State, name: &str) -> ! {
+	lua.error(format!("attempt to perform arithmetic ({}) on {} and {}", name, lua.get_type(1), lua.get_type(2)))
+}
+
+macro_rules! binary_metamethods {
+	{$($trait:ident::$op:ident => $name:ident / $name_rhs:ident / $try_name:ident / $try_name_rhs:ident, commutative = $commutative:literal;)*} => {$(
+		#[doc(hidden)]
+		/// Like the metamethod of the same name, but pushes nothing and returns `false` if the operands aren't both `T`.
+		pub unsafe fn $try_name<T>(lua: State) -> bool
+		where
+			T: LuaUserData + Clone + $trait,
+			<T as $trait>::Output: LuaUserData,
+		{
+			let (a, b) = match (lua.test_class::<T>(1), lua.test_class::<T>(2)) {
+				(Some(a), Some(b)) => (T::clone(&a), T::clone(&b)),
+				_ => return false,
+			};
+			lua.push_userdata(a.$op(b));
+			true
+		}
+
+		#[doc(hidden)]
+		/// Like the metamethod of the same name, but pushes nothing and returns `false` if the operands aren't a `T` and an `Rhs`.
+		pub unsafe fn $try_name_rhs<T, Rhs>(lua: State) -> bool
+		where
+			T: LuaUserData + Clone + $trait<Rhs>,
+			<T as $trait<Rhs>>::Output: LuaUserData,
+			Rhs: FromLua,
+		{
+			let read = |this: i32, rhs: i32| match (lua.test_class::<T>(this), Rhs::from_lua(lua, rhs)) {
+				(Some(a), Ok(b)) => Some((T::clone(&a), b)),
+				_ => None,
+			};
+			let operands = match read(1, 2) {
+				Some(operands) => Some(operands),
+				None if $commutative => read(2, 1),
+				None => None,
+			};
+			match operands {
+				Some((a, b)) => {
+					lua.push_userdata(a.$op(b));
+					true
+				},
+				None => false,
+			}
+		}
+
+		#[doc = concat!("`__", stringify!($name), "` from `", stringify!($trait), "`, where both operands are `T`")]
+		pub unsafe extern "C-unwind" fn $name<T>(lua: State) -> i32
+		where
+			T: LuaUserData + Clone + $trait,
+			<T as $trait>::Output: LuaUserData,
+		{
+			let a = T::clone(&lua.check_class::<T>(1));
+			let b = T::clone(&lua.check_class::<T>(2));
+			lua.push_userdata(a.$op(b));
+			1
+		}
+
+		#[doc = concat!("`__", stringify!($name), "` from `", stringify!($trait), "<Rhs>`, where the right operand is read with `FromLua`")]
+		#[doc = ""]
+		#[doc = concat!("Commutative: ", stringify!($commutative), ". If it is, the operands may also be the other way around (`rhs ", stringify!($name), " userdata`).")]
+		pub unsafe extern "C-unwind" fn $name_rhs<T, Rhs>(lua: State) -> i32
+		where
+			T: LuaUserData + Clone + $trait<Rhs>,
+			<T as $trait<Rhs>>::Output: LuaUserData,
+			Rhs: FromLua,
+		{
+			let (a, b) = if $commutative && lua.test_class::<T>(1).is_none() {
+				(T::clone(&lua.check_class::<T>(2)), lua.check::<Rhs>(1))
+			} else {
+				(T::clone(&lua.check_class::<T>(1)), lua.check::<Rhs>(2))
+			};
+			lua.push_userdata(a.$op(b));
+			1
+		}
+	)*};
+}
+binary_metamethods! {
+	Add::add => add / add_rhs / __try_add / __try_add_rhs, commutative = true;
+	Sub::sub => sub / sub_rhs / __try_sub / __try_sub_rhs, commutative = false;
+	Mul::mul => mul / mul_rhs / __try_mul / __try_mul_rhs, commutative = true;
+	Div::div => div / div_rhs / __try_div / __try_div_rhs, commutative = false;
+}
+
+#[doc(hidden)]
+/// Used by `#[lua_class(call = ...)]` so that closure arguments can be inferred.
+pub unsafe fn __call<T, R, F>(lua: State, f: F) -> R
+where
+	T: LuaUserData,
+	F: FnOnce(&mut T, State) -> R,
+{
+	f(&mut lua.check_class_mut::<T>(1), lua)
+}
@@ -0,0 +1,43 @@
+use std::{any::TypeId, cell::RefCell, collections::HashMap, ffi::CString};
+
+use crate::lua::State;
+
+thread_local! {
+	/// Registry names of the metatables used by `new_userdata` and `push_userdata`, so they're only formatted once per type. The flag is set for `push_userdata`'s class metatables.
+	static NAMES: RefCell<HashMap<(TypeId, bool), CString>> = RefCell::new(HashMap::new());
+}
+
+/// The registry name of `T`'s `new_userdata` metatable, or its class metatable if `class` is set, made from `label` the first time it's needed.
+///
+/// It includes this module's address as well as the type, as other gmod-rs modules loaded into the same Lua state share the registry and may have types with the same name.
+pub(super) fn with_name<T: 'static, R>(class: bool, label: impl FnOnce() -> String, f: impl FnOnce(&CString) -> R) -> R {
+	NAMES.with(|names| {
+		let mut names = names.borrow_mut();
+		let name = names.entry((TypeId::of::<T>(), class)).or_insert_with(|| {
+			CString::new(format!("gmod-rs {} ({:p})", label(), super::header::module())).unwrap()
+		});
+		f(name)
+	})
+}
+
+impl State {
+	/// Pushes the metatable shared by every `T` created by `new_userdata` without a metatable of its own, creating it first if needed.
+	///
+	/// It starts out with a `__gc` metamethod if `T` needs dropping, and `__name` set to `T`'s type name. Callers can add their own methods and metamethods to it once, and every `T` will have them.
+	pub unsafe fn userdata_metatable<T: 'static>(&self) {
+		with_name::<T, _>(false, || std::any::type_name::<T>().to_string(), |name| {
+			if !self.new_metatable(name.as_ptr()) {
+				crate::lua::forget_metatable_on_unload(*self, name);
+				self.neutralize_on_unload(-1);
+
+				self.push_string(std::any::type_name::<T>());
+				self.set_field(-2, crate::lua_string!("__name"));
+
+				if std::mem::needs_drop::<T>() {
+					self.push_function(super::__gc::<T>);
+					self.set_field(-2, crate::lua_string!("__gc"));
+				}
+			}
+		})
+	}
+}
@@ -0,0 +1,244 @@
+mod class;
+pub use class::*;
+
+pub mod meta;
+
+mod vector;
+pub use vector::Vector;
+
+mod angle;
+pub use angle::Angle;
+
+mod matrix;
+pub use matrix::Matrix;
+
+mod entity;
+pub use entity::{Entity, EntityError};
+
+mod convert;
+
+mod type_ids;
+pub use type_ids::{validate_type_ids, TypeIdMismatch};
+#[doc(hidden)]
+pub use type_ids::__validate_type_ids;
+
+mod borrow;
+pub use borrow::{BorrowError, UserDataRef, UserDataRefMut};
+pub(crate) use borrow::{BorrowFlag, BorrowState};
+
+mod header;
+pub use header::RustUserDataError;
+pub(crate) use header::finalize_all;
+pub(crate) use header::alloc as alloc_rust_userdata;
+
+mod metatable;
+
+mod handle;
+pub use handle::{Handle, HandleError, HandleMap};
+
+mod user_value;
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum UserData {
+	None = 255,
+
+	Nil = 0,
+	Bool,
+	LightUserData,
+	Number,
+	String,
+	Table,
+	Function,
+	UserData,
+	Thread,
+
+	// GMod Types
+	Entity,
+	Vector,
+	Angle,
+	PhysObj,
+	Save,
+	Restore,
+	DamageInfo,
+	EffectData,
+	MoveData,
+	RecipientFilter,
+	UserCmd,
+	ScriptedVehicle,
+	Material,
+	Panel,
+	Particle,
+	ParticleEmitter,
+	Texture,
+	UserMsg,
+	ConVar,
+	IMesh,
+	Matrix,
+	Sound,
+	PixelVisHandle,
+	DLight,
+	Video,
+	File,
+	Locomotion,
+	Path,
+	NavArea,
+	SoundHandle,
+	NavLadder,
+	ParticleSystem,
+	ProjectedTexture,
+	PhysCollide,
+	SurfaceInfo,
+
+	MAX
+}
+impl TryFrom<u8> for UserData {
+	type Error = u8;
+
+	/// Converts a raw type tag, returning it back if it isn't one of the types in `UserData`.
+	#[inline]
+	fn try_from(tag: u8) -> Result<Self, u8> {
+		if tag < UserData::MAX as u8 {
+			// SAFETY: the discriminants below `MAX` are contiguous from zero
+			Ok(unsafe { std::mem::transmute::<u8, UserData>(tag) })
+		} else if tag == UserData::None as u8 {
+			Ok(UserData::None)
+		} else {
+			Err(tag)
+		}
+	}
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct TaggedUserData
+{
+	pub data: *mut core::ffi::c_void,
+
+	/// The raw type tag, which is kept as a byte as the engine may use tags that aren't in `UserData`. Use `UserData::try_from` to convert it, or `resolved_type` to look it up from the `MetaID`s found at runtime.
+	pub r#type: u8
+}
+
+/// Why a `TaggedUserData` couldn't be borrowed as a Rust struct.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CoerceError {
+	/// The userdata is of another type, given here (see `TaggedUserData::resolved_type`)
+	WrongType(UserData),
+
+	/// The userdata is already borrowed
+	Borrow(BorrowError),
+}
+impl std::fmt::Display for CoerceError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			CoerceError::WrongType(r#type) => write!(f, "got {:?}", r#type),
+			CoerceError::Borrow(err) => err.fmt(f),
+		}
+	}
+}
+impl std::error::Error for CoerceError {}
+impl From<BorrowError> for CoerceError {
+	#[inline]
+	fn from(err: BorrowError) -> Self {
+		CoerceError::Borrow(err)
+	}
+}
+
+/// A Rust struct with the same layout as one of GMod's built-in userdata types.
+pub trait CoercibleUserData {
+	/// The type tag of userdata holding this struct.
+	const TYPE: UserData;
+}
+
+impl TaggedUserData {
+	/// Returns the type of this userdata.
+	///
+	/// Once `validate_type_ids` has run, this is looked up from the `MetaID`s found at runtime, so it's correct even if the hardcoded ids in `UserData` are out of date. Unknown tags are `UserData::None`.
+	pub fn resolved_type(&self) -> UserData {
+		type_ids::resolve_tag(self.r#type)
+	}
+
+	/// Checks that this userdata can be coerced to `T`.
+	///
+	/// The check uses the `MetaID`s found by `validate_type_ids`, so types that have been renumbered, or don't exist in this realm, return an error instead of coercing the wrong memory.
+	fn check_type<T: CoercibleUserData>(&self) -> Result<(), CoerceError> {
+		if type_ids::runtime_id(T::TYPE) == Some(self.r#type) {
+			Ok(())
+		} else {
+			Err(CoerceError::WrongType(self.resolved_type()))
+		}
+	}
+
+	/// Coerce this tagged UserData into its corresponding Rust struct and borrow it, if possible.
+	///
+	/// This will perform a type check to ensure that the tagged userdata matches the user data you are coercing to (see `validate_type_ids`). Like `RefCell::try_borrow`, it also fails if the userdata is currently mutably borrowed through another `TaggedUserData` on this thread.
+	///
+	/// The userdata must stay on the stack (or otherwise be kept alive) while the borrow is held.
+	pub fn borrow<T: CoercibleUserData>(&self) -> Result<UserDataRef<'_, T>, CoerceError> {
+		self.check_type::<T>()?;
+		Ok(unsafe { UserDataRef::new(self.data as *const T, BorrowFlag::Engine(self.data))? })
+	}
+
+	/// Coerce this tagged UserData into its corresponding Rust struct and mutably borrow it, if possible.
+	///
+	/// See `borrow`. Like `RefCell::try_borrow_mut`, this fails if the userdata is currently borrowed at all.
+	pub fn borrow_mut<T: CoercibleUserData>(&self) -> Result<UserDataRefMut<'_, T>, CoerceError> {
+		self.check_type::<T>()?;
+		Ok(unsafe { UserDataRefMut::new(self.data as *mut T, BorrowFlag::Engine(self.data))? })
+	}
+
+	/// Coerce this tagged UserData into its corresponding Rust struct, if possible.
+	///
+	/// This will perform a type check to ensure that the tagged userdata matches the user data you are coercing to, but doesn't track borrows, so coercing the same userdata twice aliases mutably.
+	///
+	/// Returns the actual type of the userdata (see `resolved_type`) on failure.
+	#[deprecated(note = "aliases mutably if called twice on the same userdata, use `borrow` or `borrow_mut` instead")]
+	#[allow(clippy::mut_from_ref)]
+	pub fn coerce<T: CoercibleUserData>(&self) -> Result<&mut T, UserData> {
+		match self.check_type::<T>() {
+			Ok(_) => Ok(unsafe { &mut *(self.data as *mut T) }),
+			Err(_) => Err(self.resolved_type()),
+		}
+	}
+
+	/// Coerce this tagged UserData into its corresponding Rust struct, if possible.
+	///
+	/// # Safety
+	/// This will NOT perform a type check to ensure that the tagged userdata matches the user data you are coercing to.
+	///
+	/// Coercing to the wrong type is undefined behaviour and is likely to crash your program.
+	pub unsafe fn coerce_unchecked<'a, T: CoercibleUserData>(&self) -> &'a mut T {
+		&mut *(self.data as *mut T)
+	}
+}
+
+macro_rules! userdata {
+	($(UserData::$enum:ident => $struct:ident),+) => {
+		$(impl CoercibleUserData for $struct {
+			const TYPE: UserData = UserData::$enum;
+		})+
+	};
+}
+userdata! {
+	UserData::Vector => Vector,
+	UserData::Angle => Angle,
+	UserData::Matrix => Matrix
+}
+
+pub(crate) unsafe extern "C-unwind" fn __gc<T: 'static>(lua: crate::lua::State) -> i32 {
+	header::finalize::<T>(lua, 1);
+	0
+}
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn user_data_try_from() {
+		assert_eq!(UserData::try_from(0), Ok(UserData::Nil));
+		assert_eq!(UserData::try_from(UserData::SurfaceInfo as u8), Ok(UserData::SurfaceInfo));
+		assert_eq!(UserData::try_from(255), Ok(UserData::None));
+		assert_eq!(UserData::try_from(UserData::MAX as u8), Err(UserData::MAX as u8));
+		assert_eq!(UserData::try_from(200), Err(200));
+	}
+}
@@ -0,0 +1,139 @@
+use std::{ffi::CStr, sync::atomic::{AtomicBool, AtomicU8, Ordering}};
+
+use crate::{cstr::cstr, lua::{State, LUA_REGISTRYINDEX}};
+
+use super::UserData;
+
+/// The registry name of the metatable of each of GMod's userdata types.
+const META_NAMES: &[(UserData, &CStr)] = &[
+	(UserData::Entity, cstr!("Entity")),
+	(UserData::Vector, cstr!("Vector")),
+	(UserData::Angle, cstr!("Angle")),
+	(UserData::PhysObj, cstr!("PhysObj")),
+	(UserData::Save, cstr!("ISave")),
+	(UserData::Restore, cstr!("IRestore")),
+	(UserData::DamageInfo, cstr!("CTakeDamageInfo")),
+	(UserData::EffectData, cstr!("CEffectData")),
+	(UserData::MoveData, cstr!("CMoveData")),
+	(UserData::RecipientFilter, cstr!("CRecipientFilter")),
+	(UserData::UserCmd, cstr!("CUserCmd")),
+	(UserData::Material, cstr!("IMaterial")),
+	(UserData::Panel, cstr!("Panel")),
+	(UserData::Particle, cstr!("CLuaParticle")),
+	(UserData::ParticleEmitter, cstr!("CLuaEmitter")),
+	(UserData::Texture, cstr!("ITexture")),
+	(UserData::UserMsg, cstr!("bf_read")),
+	(UserData::ConVar, cstr!("ConVar")),
+	(UserData::IMesh, cstr!("IMesh")),
+	(UserData::Matrix, cstr!("VMatrix")),
+	(UserData::Sound, cstr!("CSoundPatch")),
+	(UserData::PixelVisHandle, cstr!("pixelvis_handle_t")),
+	(UserData::DLight, cstr!("dlight_t")),
+	(UserData::Video, cstr!("IVideoWriter")),
+	(UserData::File, cstr!("File")),
+	(UserData::Locomotion, cstr!("CLuaLocomotion")),
+	(UserData::Path, cstr!("PathFollower")),
+	(UserData::NavArea, cstr!("CNavArea")),
+	(UserData::SoundHandle, cstr!("IGModAudioChannel")),
+	(UserData::NavLadder, cstr!("CNavLadder")),
+	(UserData::ParticleSystem, cstr!("CNewParticleEffect")),
+	(UserData::ProjectedTexture, cstr!("ProjectedTexture")),
+	(UserData::PhysCollide, cstr!("PhysCollide")),
+	(UserData::SurfaceInfo, cstr!("SurfaceInfo")),
+];
+
+/// Marks a type whose metatable doesn't exist in this realm.
+const MISSING: u8 = UserData::None as u8;
+
+static LOADED: AtomicBool = AtomicBool::new(false);
+
+/// The `MetaID` found at runtime for each `UserData`, indexed by its hardcoded id.
+static RUNTIME_IDS: [AtomicU8; UserData::MAX as usize] = [const { AtomicU8::new(MISSING) }; UserData::MAX as usize];
+
+/// A built-in userdata type whose `MetaID` isn't what gmod-rs expects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TypeIdMismatch {
+	pub r#type: UserData,
+
+	/// The id hardcoded in `UserData`
+	pub expected: u8,
+
+	/// The `MetaID` of the type's metatable
+	pub found: u8,
+}
+impl std::fmt::Display for TypeIdMismatch {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{:?} has MetaID {}, expected {}", self.r#type, self.found, self.expected)
+	}
+}
+
+/// Reads the `MetaID` of every built-in userdata type's metatable and uses it to validate `TaggedUserData::borrow` and `borrow_mut`.
+///
+/// Types that don't exist in this realm can't be coerced at all afterwards. Returns the types whose ids have changed, which means `UserData` is out of date with this branch of the game.
+///
+/// This is already done for you if you add `#[gmod::gmod13_open]` to your `gmod13_open` function.
+pub unsafe fn validate_type_ids(lua: State) -> Result<(), Vec<TypeIdMismatch>> {
+	let mut mismatches = Vec::new();
+
+	for &(r#type, name) in META_NAMES {
+		lua.get_field(LUA_REGISTRYINDEX, name.as_ptr());
+		let id = if lua.is_table(-1) {
+			lua.get_field(-1, crate::lua_string!("MetaID"));
+			let id = if lua.is_number(-1) { Some(lua.to_number(-1)) } else { None };
+			lua.pop();
+			id
+		} else {
+			None
+		};
+		lua.pop();
+
+		let id = match id {
+			Some(id) if (0.0..MISSING as f64).contains(&id) => id as u8,
+			_ => MISSING,
+		};
+		if id != MISSING && id != r#type as u8 {
+			mismatches.push(TypeIdMismatch { r#type, expected: r#type as u8, found: id });
+		}
+		RUNTIME_IDS[r#type as usize].store(id, Ordering::Relaxed);
+	}
+
+	LOADED.store(true, Ordering::Release);
+
+	if mismatches.is_empty() {
+		Ok(())
+	} else {
+		Err(mismatches)
+	}
+}
+
+#[doc(hidden)]
+/// Called by `#[gmod13_open]`
+pub unsafe fn __validate_type_ids(lua: State) {
+	if let Err(mismatches) = validate_type_ids(lua) {
+		for mismatch in mismatches {
+			eprintln!("[gmod-rs] userdata type id mismatch: {}", mismatch);
+		}
+	}
+}
+
+/// The type tag that userdata of this type actually have, if it exists in this realm.
+pub(crate) fn runtime_id(r#type: UserData) -> Option<u8> {
+	if r#type < UserData::Entity || r#type >= UserData::MAX || !LOADED.load(Ordering::Acquire) {
+		return Some(r#type as u8);
+	}
+	match RUNTIME_IDS[r#type as usize].load(Ordering::Relaxed) {
+		MISSING => None,
+		id => Some(id),
+	}
+}
+
+/// The type that a userdata with this tag actually is.
+pub(crate) fn resolve_tag(tag: u8) -> UserData {
+	if !LOADED.load(Ordering::Acquire) || tag < UserData::Entity as u8 {
+		return UserData::try_from(tag).unwrap_or(UserData::None);
+	}
+	META_NAMES.iter()
+		.map(|&(r#type, _)| r#type)
+		.find(|&r#type| RUNTIME_IDS[r#type as usize].load(Ordering::Relaxed) == tag)
+		.unwrap_or(UserData::None)
+}
@@ -0,0 +1,132 @@
+use std::ffi::c_void;
+
+use crate::lua::{FromLua, FromLuaError, PushToLua, State};
+
+/// Marks environment tables created for user values, as userdata start out sharing the environment of the function that created them (usually `_G`). Only its address is used.
+static USER_VALUES_KEY: u8 = 0;
+
+/// Pushes the user value table of the userdata at `index`, or nothing if it doesn't have one yet.
+unsafe fn push_user_values(lua: State, index: i32) -> bool {
+	lua.get_fenv(index);
+	if lua.is_table(-1) {
+		lua.push_lightuserdata(&USER_VALUES_KEY as *const u8 as *mut c_void);
+		lua.raw_get(-2);
+		let ours = !lua.is_nil(-1);
+		lua.pop();
+		if ours {
+			return true;
+		}
+	}
+	lua.pop();
+	false
+}
+
+unsafe fn push_key(lua: State, name: Option<&str>) {
+	match name {
+		Some(name) => lua.push_string(name),
+		None => lua.push_integer(1),
+	}
+}
+
+unsafe fn set_user_value(lua: State, index: i32, name: Option<&str>) {
+	let index = lua.abs_index(index);
+	if !lua.is_userdata(index) {
+		lua.error("user values can only be set on full userdata");
+	}
+
+	if !push_user_values(lua, index) {
+		lua.create_table(1, 1);
+		lua.push_lightuserdata(&USER_VALUES_KEY as *const u8 as *mut c_void);
+		lua.push_boolean(true);
+		lua.raw_set(-3);
+		lua.push_value(-1);
+		if !lua.set_fenv(index) {
+			lua.error("user values can only be set on full userdata");
+		}
+	}
+
+	push_key(lua, name);
+	lua.push_value(-3);
+	lua.raw_set(-3);
+	lua.pop_n(2);
+}
+
+unsafe fn get_user_value(lua: State, index: i32, name: Option<&str>) {
+	let index = lua.abs_index(index);
+	if !lua.is_userdata(index) || !push_user_values(lua, index) {
+		lua.push_nil();
+		return;
+	}
+
+	push_key(lua, name);
+	lua.raw_get(-2);
+	lua.remove(-2);
+}
+
+/// Fails to compile for types like `AnyValue`, which would refer to the user value's stack slot after it's popped, when a `read_*user_value` function is instantiated with them.
+struct AssertOwned<V>(std::marker::PhantomData<V>);
+impl<V: FromLua> AssertOwned<V> {
+	const OK: () = assert!(!V::BORROWS_STACK, "user values are popped once they're read, so they can't be read as AnyValue, use get_user_value instead");
+}
+
+impl State {
+	/// Pops a value from the stack and attaches it to the userdata at the given index.
+	///
+	/// The value is stored in the userdata's environment table, so the garbage collector can see it: anything only referenced from user values is collected along with the userdata, even if it references the userdata back. Prefer this over registry references for callbacks and back-references held by Rust userdata.
+	pub unsafe fn set_user_value(&self, index: i32) {
+		set_user_value(*self, index, None)
+	}
+
+	/// Pushes the value attached to the userdata at the given index with `set_user_value`, or `nil` if there isn't one.
+	pub unsafe fn get_user_value(&self, index: i32) {
+		get_user_value(*self, index, None)
+	}
+
+	/// Like `set_user_value`, but any number of values can be attached by name.
+	pub unsafe fn set_named_user_value(&self, index: i32, name: &str) {
+		set_user_value(*self, index, Some(name))
+	}
+
+	/// Pushes the value attached to the userdata at the given index with `set_named_user_value`, or `nil` if there isn't one.
+	pub unsafe fn get_named_user_value(&self, index: i32, name: &str) {
+		get_user_value(*self, index, Some(name))
+	}
+
+	/// Attaches `value` to the userdata at the given index. See `set_user_value`.
+	pub unsafe fn write_user_value<V: PushToLua>(&self, index: i32, value: V) {
+		let index = self.abs_index(index);
+		value.push_to_lua(*self);
+		self.set_user_value(index);
+	}
+
+	/// Reads the value attached to the userdata at the given index. See `get_user_value`.
+	///
+	/// The value is popped once it's read, so this fails to compile for `AnyValue`, which only refers to a stack slot. Use `get_user_value` to leave the value on the stack instead.
+	pub unsafe fn read_user_value<V: FromLua>(&self, index: i32) -> Result<V, FromLuaError> {
+		#[allow(clippy::let_unit_value)]
+		let () = AssertOwned::<V>::OK;
+		self.get_user_value(index);
+		let value = V::from_lua(*self, -1);
+		self.pop();
+		value
+	}
+
+	/// Attaches `value` to the userdata at the given index by name. See `set_named_user_value`.
+	pub unsafe fn write_named_user_value<V: PushToLua>(&self, index: i32, name: &str, value: V) {
+		let index = self.abs_index(index);
+		value.push_to_lua(*self);
+		self.set_named_user_value(index, name);
+	}
+
+	/// Reads a value attached to the userdata at the given index by name. See `get_named_user_value`.
+	///
+	/// Like `read_user_value`, this fails to compile for `AnyValue`.
+	pub unsafe fn read_named_user_value<V: FromLua>(&self, index: i32, name: &str) -> Result<V, FromLuaError> {
+		#[allow(clippy::let_unit_value)]
+		let () = AssertOwned::<V>::OK;
+		self.get_named_user_value(index, name);
+		let value = V::from_lua(*self, -1);
+		self.pop();
+		value
+	}
+}
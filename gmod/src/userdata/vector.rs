@@ -0,0 +1,280 @@
+use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+
+use super::Angle;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd)]
+#[repr(C)]
+pub struct Vector {
+	pub x: f32,
+	pub y: f32,
+	pub z: f32
+}
+
+impl Vector {
+	pub const ZERO: Vector = Vector::new(0.0, 0.0, 0.0);
+
+	#[inline]
+	pub const fn new(x: f32, y: f32, z: f32) -> Vector {
+		Vector { x, y, z }
+	}
+
+	#[inline]
+	/// `Vector:Length()`
+	pub fn length(&self) -> f32 {
+		self.length_sqr().sqrt()
+	}
+
+	#[inline]
+	/// `Vector:LengthSqr()`
+	pub fn length_sqr(&self) -> f32 {
+		self.x * self.x + self.y * self.y + self.z * self.z
+	}
+
+	#[inline]
+	/// `Vector:Length2D()`
+	pub fn length_2d(&self) -> f32 {
+		self.length_2d_sqr().sqrt()
+	}
+
+	#[inline]
+	/// `Vector:Length2DSqr()`
+	pub fn length_2d_sqr(&self) -> f32 {
+		self.x * self.x + self.y * self.y
+	}
+
+	#[inline]
+	/// `Vector:Distance(other)`
+	pub fn distance(&self, other: Vector) -> f32 {
+		(*self - other).length()
+	}
+
+	#[inline]
+	/// `Vector:DistToSqr(other)`
+	pub fn distance_sqr(&self, other: Vector) -> f32 {
+		(*self - other).length_sqr()
+	}
+
+	/// `Vector:Normalize()`
+	///
+	/// Normalizes this vector in place and returns its previous length. Like the engine's `VectorNormalize`, a zero vector stays zero.
+	pub fn normalize(&mut self) -> f32 {
+		let length = self.length();
+		let inv = 1.0 / (length + f32::EPSILON);
+		*self *= inv;
+		length
+	}
+
+	#[inline]
+	#[must_use]
+	/// `Vector:GetNormalized()`
+	pub fn normalized(mut self) -> Vector {
+		self.normalize();
+		self
+	}
+
+	#[inline]
+	/// `Vector:Dot(other)`
+	pub fn dot(&self, other: Vector) -> f32 {
+		self.x * other.x + self.y * other.y + self.z * other.z
+	}
+
+	#[inline]
+	/// `Vector:Cross(other)`
+	pub fn cross(&self, other: Vector) -> Vector {
+		Vector {
+			x: self.y * other.z - self.z * other.y,
+			y: self.z * other.x - self.x * other.z,
+			z: self.x * other.y - self.y * other.x,
+		}
+	}
+
+	#[inline]
+	/// `LerpVector(t, self, to)`
+	pub fn lerp(&self, to: Vector, t: f32) -> Vector {
+		*self + (to - *self) * t
+	}
+
+	#[inline]
+	/// `Vector:Rotate(angle)`, but returns the rotated vector instead of modifying it in place.
+	pub fn rotated(&self, angle: Angle) -> Vector {
+		let matrix = angle.matrix();
+		Vector {
+			x: self.dot(matrix[0]),
+			y: self.dot(matrix[1]),
+			z: self.dot(matrix[2]),
+		}
+	}
+
+	#[inline]
+	/// `Vector:Rotate(angle)`
+	pub fn rotate(&mut self, angle: Angle) {
+		*self = self.rotated(angle);
+	}
+
+	/// `Vector:Angle()`
+	///
+	/// Returns the angle pointing in this vector's direction, with zero roll.
+	pub fn angle(&self) -> Angle {
+		if self.x == 0.0 && self.y == 0.0 {
+			return Angle::new(if self.z > 0.0 { 270.0 } else { 90.0 }, 0.0, 0.0);
+		}
+
+		let mut yaw = ((self.y as f64).atan2(self.x as f64) * 180.0 / std::f64::consts::PI) as f32;
+		if yaw < 0.0 {
+			yaw += 360.0;
+		}
+
+		let length_2d = self.length_2d();
+		let mut pitch = ((-self.z as f64).atan2(length_2d as f64) * 180.0 / std::f64::consts::PI) as f32;
+		if pitch < 0.0 {
+			pitch += 360.0;
+		}
+
+		Angle::new(pitch, yaw, 0.0)
+	}
+
+	#[inline]
+	/// `Vector:IsZero()`
+	pub fn is_zero(&self) -> bool {
+		self.x == 0.0 && self.y == 0.0 && self.z == 0.0
+	}
+
+	#[inline]
+	/// `Vector:IsEqualTol(other, tolerance)`
+	pub fn is_equal_tol(&self, other: Vector, tolerance: f32) -> bool {
+		(self.x - other.x).abs() <= tolerance
+			&& (self.y - other.y).abs() <= tolerance
+			&& (self.z - other.z).abs() <= tolerance
+	}
+}
+
+impl Add for Vector {
+	type Output = Vector;
+
+	#[inline]
+	fn add(self, rhs: Vector) -> Vector {
+		Vector::new(self.x + rhs.x, self.y + rhs.y, self.z + rhs.z)
+	}
+}
+impl Sub for Vector {
+	type Output = Vector;
+
+	#[inline]
+	fn sub(self, rhs: Vector) -> Vector {
+		Vector::new(self.x - rhs.x, self.y - rhs.y, self.z - rhs.z)
+	}
+}
+impl Mul for Vector {
+	type Output = Vector;
+
+	#[inline]
+	fn mul(self, rhs: Vector) -> Vector {
+		Vector::new(self.x * rhs.x, self.y * rhs.y, self.z * rhs.z)
+	}
+}
+impl Mul<f32> for Vector {
+	type Output = Vector;
+
+	#[inline]
+	fn mul(self, rhs: f32) -> Vector {
+		Vector::new(self.x * rhs, self.y * rhs, self.z * rhs)
+	}
+}
+impl Mul<Vector> for f32 {
+	type Output = Vector;
+
+	#[inline]
+	fn mul(self, rhs: Vector) -> Vector {
+		rhs * self
+	}
+}
+impl Div for Vector {
+	type Output = Vector;
+
+	#[inline]
+	fn div(self, rhs: Vector) -> Vector {
+		Vector::new(self.x / rhs.x, self.y / rhs.y, self.z / rhs.z)
+	}
+}
+impl Div<f32> for Vector {
+	type Output = Vector;
+
+	#[inline]
+	/// Multiplies by the reciprocal, like the engine does.
+	fn div(self, rhs: f32) -> Vector {
+		self * (1.0 / rhs)
+	}
+}
+impl Neg for Vector {
+	type Output = Vector;
+
+	#[inline]
+	fn neg(self) -> Vector {
+		Vector::new(-self.x, -self.y, -self.z)
+	}
+}
+
+macro_rules! assign_ops {
+	($($trait:ident::$fn:ident => $op:ident<$rhs:ty>),*) => {$(
+		impl $trait<$rhs> for Vector {
+			#[inline]
+			fn $fn(&mut self, rhs: $rhs) {
+				*self = self.$op(rhs);
+			}
+		}
+	)*};
+}
+assign_ops! {
+	AddAssign::add_assign => add<Vector>,
+	SubAssign::sub_assign => sub<Vector>,
+	MulAssign::mul_assign => mul<Vector>,
+	MulAssign::mul_assign => mul<f32>,
+	DivAssign::div_assign => div<Vector>,
+	DivAssign::div_assign => div<f32>
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn normalize() {
+		let mut v = Vector::new(3.0, 4.0, 0.0);
+		assert_eq!(v.normalize(), 5.0);
+		assert!(v.is_equal_tol(Vector::new(0.6, 0.8, 0.0), 1e-6));
+		assert!((v.length() - 1.0).abs() < 1e-6);
+
+		let mut zero = Vector::ZERO;
+		assert_eq!(zero.normalize(), 0.0);
+		assert_eq!(zero, Vector::ZERO);
+
+		assert!(Vector::new(0.0, 0.0, -2.0).normalized().is_equal_tol(Vector::new(0.0, 0.0, -1.0), 1e-6));
+	}
+
+	#[test]
+	fn vector_angles() {
+		assert_eq!(Vector::new(1.0, 0.0, 0.0).angle(), Angle::new(0.0, 0.0, 0.0));
+		assert_eq!(Vector::new(0.0, 1.0, 0.0).angle(), Angle::new(0.0, 90.0, 0.0));
+		assert_eq!(Vector::new(0.0, -1.0, 0.0).angle(), Angle::new(0.0, 270.0, 0.0));
+		assert_eq!(Vector::new(-1.0, 0.0, 0.0).angle(), Angle::new(0.0, 180.0, 0.0));
+		assert_eq!(Vector::new(0.0, 0.0, 1.0).angle(), Angle::new(270.0, 0.0, 0.0));
+		assert_eq!(Vector::new(0.0, 0.0, -1.0).angle(), Angle::new(90.0, 0.0, 0.0));
+		assert!(Vector::new(1.0, 0.0, 1.0).angle().is_equal_tol(Angle::new(315.0, 0.0, 0.0), 1e-4));
+		assert!(Vector::new(1.0, 1.0, -1.0).angle().is_equal_tol(Angle::new(35.26439, 45.0, 0.0), 1e-4));
+	}
+
+	#[test]
+	fn angles_round_trip() {
+		for angle in [Angle::new(30.0, 45.0, 0.0), Angle::new(-60.0, 170.0, 0.0), Angle::new(10.0, -100.0, 0.0)] {
+			let back = angle.forward().angle();
+			assert!((back - angle).normalized().is_equal_tol(Angle::ZERO, 1e-3), "{:?} came back as {:?}", angle, back);
+		}
+	}
+
+	#[test]
+	fn rotate() {
+		assert!(Vector::new(1.0, 0.0, 0.0).rotated(Angle::new(0.0, 90.0, 0.0)).is_equal_tol(Vector::new(0.0, 1.0, 0.0), 1e-6));
+		assert!(Vector::new(1.0, 0.0, 0.0).rotated(Angle::new(90.0, 0.0, 0.0)).is_equal_tol(Vector::new(0.0, 0.0, -1.0), 1e-6));
+		assert!(Vector::new(0.0, 1.0, 0.0).rotated(Angle::new(0.0, 0.0, 90.0)).is_equal_tol(Vector::new(0.0, 0.0, 1.0), 1e-6));
+	}
+}
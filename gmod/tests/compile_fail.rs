@@ -0,0 +1,8 @@
+//! Checks that misusing the macros produces readable errors, pointing at the offending code, and that the trickier valid uses compile.
+
+#[test]
+fn compile_fail() {
+	let t = trybuild::TestCases::new();
+	t.compile_fail("tests/ui/*.rs");
+	t.pass("tests/ui/pass/*.rs");
+}
@@ -0,0 +1,6 @@
+#[gmod::gmod13_close]
+fn gmod13_close(lua: gmod::lua::State, extra: i32) -> i32 {
+	0
+}
+
+fn main() {}
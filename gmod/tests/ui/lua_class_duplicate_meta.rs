@@ -0,0 +1,14 @@
+#[gmod::lua_class(meta(Mul<f64>, PartialEq, Mul<f64>))]
+#[derive(Clone, Copy, PartialEq)]
+struct Thing {
+	x: f64,
+}
+
+impl std::ops::Mul<f64> for Thing {
+	type Output = Thing;
+	fn mul(self, rhs: f64) -> Thing {
+		Thing { x: self.x * rhs }
+	}
+}
+
+fn main() {}
@@ -0,0 +1,6 @@
+#[gmod::lua_class(meta(Hash))]
+struct Thing {
+	x: i32,
+}
+
+fn main() {}
@@ -0,0 +1,6 @@
+#[gmod::lua_export("mymod..func")]
+fn func(x: f64) -> f64 {
+	x
+}
+
+fn main() {}
@@ -0,0 +1,6 @@
+#[gmod::lua_function]
+extern "C" fn hello(lua: gmod::lua::State) -> i32 {
+	0
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+pub struct Counter {
+	count: i32,
+}
+impl Counter {
+	#[gmod::lua_function(associated)]
+	fn get(&self) -> i32 {
+		self.count
+	}
+}
+
+fn main() {}
@@ -0,0 +1,6 @@
+#[gmod::lua_function]
+fn method(self, x: f64) -> f64 {
+	x
+}
+
+fn main() {}
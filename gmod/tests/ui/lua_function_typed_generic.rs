@@ -0,0 +1,6 @@
+#[gmod::lua_function]
+fn identity<T: gmod::lua::FromLua>(value: T) -> T {
+	value
+}
+
+fn main() {}
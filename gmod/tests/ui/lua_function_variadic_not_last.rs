@@ -0,0 +1,6 @@
+#[gmod::lua_function]
+fn join(parts: gmod::lua::Variadic<String>, separator: String) -> String {
+	parts.join(&separator)
+}
+
+fn main() {}
@@ -0,0 +1,4 @@
+#[gmod::lua_module(global)]
+mod mymod {}
+
+fn main() {}
@@ -0,0 +1,19 @@
+#[gmod::lua_module]
+mod mymod {
+	mod math {
+		#[gmod::lua_function]
+		fn add(a: f64, b: f64) -> f64 {
+			a + b
+		}
+	}
+
+	#[lua(skip)]
+	mod skipped {
+		#[gmod::lua_function]
+		fn sub(a: f64, b: f64) -> f64 {
+			a - b
+		}
+	}
+}
+
+fn main() {}
@@ -0,0 +1,6 @@
+#[gmod::gmod13_open]
+async fn gmod13_open(lua: gmod::lua::State) -> i32 {
+	0
+}
+
+fn main() {}
@@ -0,0 +1,6 @@
+#[gmod::gmod13_open]
+fn gmod13_open() -> i32 {
+	0
+}
+
+fn main() {}
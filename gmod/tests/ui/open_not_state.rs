@@ -0,0 +1,6 @@
+#[gmod::gmod13_open]
+fn gmod13_open(lua: *mut std::ffi::c_void) -> i32 {
+	0
+}
+
+fn main() {}
@@ -0,0 +1,25 @@
+#[gmod::lua_class(meta(Mul, Mul<f64>))]
+#[derive(Clone, Copy)]
+struct Thing {
+	x: f64,
+}
+
+impl std::ops::Mul for Thing {
+	type Output = Thing;
+	fn mul(self, rhs: Thing) -> Thing {
+		Thing { x: self.x * rhs.x }
+	}
+}
+
+impl std::ops::Mul<f64> for Thing {
+	type Output = Thing;
+	fn mul(self, rhs: f64) -> Thing {
+		Thing { x: self.x * rhs }
+	}
+}
+
+fn main() {
+	let thing = Thing { x: 2.0 };
+	assert_eq!((thing * thing).x, 4.0);
+	assert_eq!((thing * 3.0).x, 6.0);
+}
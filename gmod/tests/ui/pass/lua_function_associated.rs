@@ -0,0 +1,27 @@
+use gmod::lua::{LuaFunction, PushToLua, State};
+
+pub struct Counter {
+	count: i32,
+}
+impl PushToLua for Counter {
+	unsafe fn push_to_lua(self, lua: State) {
+		lua.push_integer(self.count as _);
+	}
+}
+impl Counter {
+	#[gmod::lua_function(associated)]
+	fn version() -> i32 {
+		1
+	}
+
+	#[gmod::lua_function(associated)]
+	fn make(count: i32) -> Counter {
+		Counter { count }
+	}
+}
+
+fn main() {
+	assert_eq!(Counter::version(), 1);
+	assert_eq!(Counter::make(2).count, 2);
+	let _: [LuaFunction; 2] = [Counter::lua_version, Counter::lua_make];
+}
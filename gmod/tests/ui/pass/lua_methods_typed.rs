@@ -0,0 +1,28 @@
+use gmod::lua::LuaFunction;
+
+#[gmod::lua_class]
+pub struct Counter {
+	count: i32,
+}
+
+#[gmod::lua_methods]
+impl Counter {
+	#[gmod::lua_function]
+	fn add(&mut self, n: i32) -> i32 {
+		self.count += n;
+		self.count
+	}
+
+	#[gmod::lua_function]
+	#[lua(name = "New")]
+	fn new(count: i32) -> Result<i32, String> {
+		Ok(count)
+	}
+}
+
+fn main() {
+	let mut counter = Counter { count: 1 };
+	assert_eq!(counter.add(2), 3);
+	assert_eq!(Counter::new(4), Ok(4));
+	let _: [LuaFunction; 2] = [Counter::lua_add, Counter::lua_new];
+}
@@ -46,10 +46,113 @@ macro_rules! drop_me {
 	};
 }
 
+#[lua_function]
+fn clamp(x: f64, min: Option<f64>, max: Option<f64>, rest: gmod::lua::Variadic<gmod::lua::AnyValue>) -> (f64, i32) {
+	(x.max(min.unwrap_or(f64::MIN)).min(max.unwrap_or(f64::MAX)), rest.len() as i32)
+}
+
+#[lua_module(name = "gmod_rs_test", loaded)]
+mod test_module {
+	pub const ANSWER: i32 = 42;
+
+	pub mod math {
+		#[lua_function]
+		fn add(a: f64, b: f64) -> f64 {
+			a + b
+		}
+	}
+}
+
+#[lua_export("GMOD_RS_EXPORTED.negate")]
+fn negate(x: f64) -> f64 {
+	-x
+}
+
+#[lua_export("GMOD_RS_EXPORTED.twice")]
+fn twice_a() {}
+
+#[lua_export("GMOD_RS_EXPORTED.twice")]
+fn twice_b() {}
+
+pub struct SetOnDrop(std::rc::Rc<std::cell::Cell<bool>>, String);
+impl Drop for SetOnDrop {
+	fn drop(&mut self) {
+		self.0.set(true);
+	}
+}
+
+pub struct Counter {
+	count: i32
+}
+impl Counter {
+	const START: i32 = 10;
+
+	#[lua_function(associated)]
+	fn start() -> i32 {
+		Self::START
+	}
+
+	#[lua_function]
+	fn add(&mut self, n: i32) -> i32 {
+		self.count += n;
+		self.count
+	}
+
+	#[lua_function]
+	unsafe fn get(&self, lua: gmod::lua::State) -> i32 {
+		lua.push_integer(self.count as _);
+		1
+	}
+}
+
+#[lua_class]
+pub struct Reloaded {
+	dropped: std::rc::Rc<std::cell::Cell<bool>>
+}
+impl Drop for Reloaded {
+	fn drop(&mut self) {
+		self.dropped.set(true);
+	}
+}
+
+#[lua_methods]
+impl Reloaded {
+	#[lua_function]
+	#[lua(name = "Ping")]
+	fn ping(&self) -> i32 {
+		1
+	}
+}
+
 #[gmod13_open]
 unsafe fn gmod13_open(lua: gmod::lua::State) -> i32 {
 	let ud = lua.new_userdata(drop_me!(), None);
 	assert_eq!(&*ud, Box::leak(Box::new(drop_me!())));
+	assert_eq!(lua.try_rust_userdata::<DropMe>(-1).map(|ud| &*ud as *const DropMe), Ok(ud as *const DropMe));
+	assert_eq!(lua.try_rust_userdata::<i32>(-1).err(), Some(gmod::userdata::RustUserDataError::WrongType));
+
+	{
+		let borrowed = lua.try_rust_userdata::<DropMe>(-1).unwrap();
+		assert_eq!(lua.try_rust_userdata_mut::<DropMe>(-1).err(), Some(gmod::userdata::RustUserDataError::Borrow(gmod::userdata::BorrowError::AlreadyBorrowed)));
+		drop(borrowed);
+
+		let borrowed = lua.try_rust_userdata_mut::<DropMe>(-1).unwrap();
+		assert_eq!(lua.try_rust_userdata::<DropMe>(-1).err(), Some(gmod::userdata::RustUserDataError::Borrow(gmod::userdata::BorrowError::AlreadyMutablyBorrowed)));
+		drop(borrowed);
+	}
+
+	assert_ne!(lua.get_metatable(-1), 0);
+	lua.userdata_metatable::<DropMe>();
+	assert!(lua.raw_equal(-1, -2));
+	lua.pop_n(2);
+
+	lua.get_user_value(-1);
+	assert!(lua.is_nil(-1));
+	lua.pop();
+	lua.write_user_value(-1, "Hello");
+	lua.write_named_user_value(-1, "answer", 42);
+	assert_eq!(lua.read_user_value::<String>(-1).as_deref(), Ok("Hello"));
+	assert_eq!(lua.read_named_user_value::<i32>(-1, "answer"), Ok(42));
 
 	lua.set_global(lua_string!("GMOD_RUST_DROP_TEST"));
 
@@ -62,13 +165,179 @@ unsafe fn gmod13_open(lua: gmod::lua::State) -> i32 {
 	lua.call(0, 0);
 	lua.call(0, 0);
 
+	let dropped = std::rc::Rc::new(std::cell::Cell::new(false));
+	lua.new_userdata(SetOnDrop(dropped.clone(), "alive".to_string()), None);
+	let borrowed = lua.try_rust_userdata::<SetOnDrop>(-1).unwrap();
+	lua.pop();
+	lua.get_global(lua_string!("collectgarbage"));
+	lua.push_value(-1);
+	lua.call(0, 0);
+	lua.call(0, 0);
+	assert!(!dropped.get());
+	assert_eq!(borrowed.1, "alive");
+	drop(borrowed);
+	assert!(dropped.get());
+
 	let ud = lua.new_userdata(420_i32, None);
 	assert_eq!(*ud, 420_i32);
 
+	let boxed: Box<dyn std::any::Any> = Box::new("boxed".to_string());
+	let ud = lua.new_boxed_userdata(boxed, None);
+	assert_eq!(lua.try_boxed_userdata::<dyn std::any::Any>(-1).map(|ud| &*ud as *const dyn std::any::Any as *const u8), Ok(ud as *const u8));
+	assert_eq!(lua.check_boxed_userdata::<dyn std::any::Any>(-1).downcast_ref::<String>().map(String::as_str), Some("boxed"));
+	assert_eq!(lua.try_rust_userdata::<String>(-1).err(), Some(gmod::userdata::RustUserDataError::WrongType));
+	lua.pop();
+
+	let mut calls = 0;
+	lua.push_rust_closure(move |lua| {
+		calls += 1;
+		lua.push_integer(calls);
+		1
+	});
+	lua.push_value(-1);
+	lua.call(0, 0);
+	lua.call(0, 1);
+	assert_eq!(lua.to_number(-1), 2.0);
+	lua.pop();
+
+	let scale = 2.0;
+	lua.push_typed_closure(move |(x, y): (f64, f64)| (x * scale, y * scale));
+	lua.push_number(1.0);
+	lua.push_number(2.0);
+	lua.call(2, 2);
+	assert_eq!((lua.to_number(-2), lua.to_number(-1)), (2.0, 4.0));
+	lua.pop_n(2);
+
+	lua.get_global(lua_string!("pcall"));
+	lua.push_typed_closure(|x: f64| if x < 0.0 { Err("negative") } else { Ok(x.sqrt()) });
+	lua.push_value(-2);
+	lua.push_value(-2);
+	lua.push_number(4.0);
+	lua.call(2, 2);
+	assert_eq!((lua.get_boolean(-2), lua.to_number(-1)), (true, 2.0));
+	lua.pop_n(2);
+	lua.push_number(-1.0);
+	lua.call(2, 2);
+	assert!(!lua.get_boolean(-2) && lua.get_string(-1).is_some_and(|err| err.contains("negative")));
+	lua.pop_n(2);
+
+	lua.load_string(lua_string!("return select(2, pcall(...))")).unwrap();
+	lua.push_typed_closure(|x: f64| x);
+	lua.push_string("not a number");
+	lua.call(2, 1);
+	assert!(lua.get_string(-1).is_some_and(|err| err.contains("bad argument #1 to 'rust_closure'")));
+	lua.pop();
+
+	test_module::register(lua);
+	lua.load_string(lua_string!("return gmod_rs_test.math.add(gmod_rs_test.ANSWER, 1), package.loaded.gmod_rs_test == gmod_rs_test")).unwrap();
+	lua.call(0, 2);
+	assert_eq!((lua.to_number(-2), lua.get_boolean(-1)), (43.0, true));
+	lua.pop_n(2);
+
+	lua.load_string(lua_string!("return GMOD_RS_EXPORTED.negate(1), GMOD_RS_EXPORTED.twice == nil")).unwrap();
+	lua.call(0, 2);
+	assert_eq!((lua.to_number(-2), lua.get_boolean(-1)), (-1.0, true));
+	lua.pop_n(2);
+
+	lua.push_function(clamp);
+	lua.push_number(5.0);
+	lua.push_nil();
+	lua.push_number(2.0);
+	lua.call(3, 2);
+	assert_eq!((lua.to_number(-2), lua.to_number(-1)), (2.0, 0.0));
+	lua.pop_n(2);
+
+	lua.load_string(lua_string!("return { 1, 2, x = \"hello\", t = { true } }, print")).unwrap();
+	lua.call(0, 2);
+	let value = lua.get_value(-2);
+	assert_eq!(value, gmod::lua::Value::Table(vec![
+		(gmod::lua::Value::String(b"t".to_vec()), gmod::lua::Value::Table(vec![(gmod::lua::Value::Number(1.0), gmod::lua::Value::Bool(true))])),
+		(gmod::lua::Value::Number(2.0), gmod::lua::Value::Number(2.0)),
+		(gmod::lua::Value::String(b"x".to_vec()), gmod::lua::Value::String(b"hello".to_vec())),
+		(gmod::lua::Value::Number(1.0), gmod::lua::Value::Number(1.0)),
+	]));
+	assert_ne!(value, gmod::lua::Value::Table(vec![(gmod::lua::Value::Number(1.0), gmod::lua::Value::Number(1.0))]));
+	let print = lua.get_value(-1);
+	assert_eq!(print, print.clone());
+	assert_ne!(print, value);
+	lua.pop_n(2);
+	gmod::lua::PushToLua::push_to_lua(&value, lua);
+	assert_eq!(lua.get_value(-1), value);
+	lua.pop();
+	assert_eq!(gmod::lua::LuaRef::from_stack(lua, gmod::lua::LUA_GLOBALSINDEX), gmod::lua::LuaRef::from_stack(lua, gmod::lua::LUA_GLOBALSINDEX));
+	let refs = [1.0, 2.0, 1.0].map(|n| { lua.push_number(n); gmod::lua::LuaRef::new(lua) });
+	assert_ne!(refs[0], refs[1]);
+	assert_eq!(refs[0], refs[2]);
+	lua.push_boolean(true);
+	let yes = gmod::lua::LuaRef::new(lua);
+	lua.push_boolean(false);
+	assert_ne!(yes, gmod::lua::LuaRef::new(lua));
+
+	lua.load_string(lua_string!("return { 1, 2, 3 }, { p = 4, y = 5, r = 6 }")).unwrap();
+	lua.call(0, 2);
+	assert_eq!(lua.check::<gmod::userdata::Vector>(-2), gmod::userdata::Vector::new(1.0, 2.0, 3.0));
+	assert_eq!(lua.check::<gmod::userdata::Angle>(-1), gmod::userdata::Angle::new(4.0, 5.0, 6.0));
+	lua.pop_n(2);
+
+	assert_eq!(Counter::start(), 10);
+	lua.push_function(Counter::lua_start);
+	lua.call(0, 1);
+	let start = lua.to_number(-1) as i32;
+	lua.pop();
+	lua.new_userdata(Counter { count: start }, None);
+	lua.push_function(Counter::lua_add);
+	lua.push_value(-2);
+	lua.push_integer(5);
+	lua.call(2, 1);
+	lua.push_function(Counter::lua_get);
+	lua.push_value(-3);
+	lua.call(1, 1);
+	assert_eq!((lua.to_number(-2), lua.to_number(-1)), (15.0, 15.0));
+	lua.pop_n(3);
+
+	lua.new_table();
+	let weak = gmod::lua::LuaWeakRef::new(lua);
+	lua.push_number(1.0);
+	let number = gmod::lua::LuaWeakRef::new(lua);
+
 	lua.get_global(lua_string!("collectgarbage"));
 	lua.push_value(-1);
 	lua.call(0, 0);
 	lua.call(0, 0);
 
+	assert!(!weak.upgrade(lua));
+	assert!(number.is_alive(lua));
+
+	// A new reference mustn't take over the slot of a collected one
+	lua.new_table();
+	let table = gmod::lua::LuaRef::from_stack(lua, -1);
+	let reused = gmod::lua::LuaWeakRef::new(lua);
+	assert!(!weak.upgrade(lua));
+	drop(weak);
+	assert_eq!(reused.to_strong(lua), Some(table));
+
+	// Nothing this module gave to Lua can call into it once it's unloaded, wherever it's been stored
+	lua.push_rust_closure(|_| 0);
+	lua.set_global(lua_string!("GMOD_RS_CLOSURE"));
+	let dropped = std::rc::Rc::new(std::cell::Cell::new(false));
+	lua.push_userdata(Reloaded { dropped: dropped.clone() });
+	lua.set_global(lua_string!("GMOD_RS_RELOADED"));
+	gmod::lua::__unload(lua);
+	assert!(dropped.get());
+	lua.load_string(lua_string!("return (pcall(GMOD_RS_EXPORTED.negate, 1)), (pcall(GMOD_RS_CLOSURE)), (pcall(gmod_rs_test.math.add, 1, 2))")).unwrap();
+	lua.call(0, 3);
+	assert_eq!((lua.get_boolean(-3), lua.get_boolean(-2), lua.get_boolean(-1)), (false, false, false));
+	lua.pop_n(3);
+
+	// Loading the module again creates new metatables instead of reusing the neutralized ones
+	let dropped = std::rc::Rc::new(std::cell::Cell::new(false));
+	lua.push_userdata(Reloaded { dropped: dropped.clone() });
+	lua.set_global(lua_string!("GMOD_RS_RELOADED"));
+	lua.load_string(lua_string!("local ping = GMOD_RS_RELOADED:Ping() GMOD_RS_RELOADED = nil collectgarbage() collectgarbage() return ping")).unwrap();
+	lua.call(0, 1);
+	assert_eq!(lua.to_number(-1), 1.);
+	lua.pop();
+	assert!(dropped.get());
+
 	0
 }
\ No newline at end of file